@@ -0,0 +1,294 @@
+use std::cmp::{self, Ordering};
+use http::{Request};
+use super::{ReadHeader, ContentType, CodecError, CodecErrorKind, CodecResult};
+
+#[derive(Debug, Clone)]
+pub struct MediaRange<'a> {
+    content_type: ContentType<'a>,
+    // quality in thousandths
+    quality: u16,
+}
+
+// qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )
+fn parse_quality(src: &str) -> Option<u16> {
+    let mut parts = src.splitn(2, '.');
+    let int = match parts.next() {
+        Some("0") => 0,
+        Some("1") => 1000,
+        _ => return None,
+    };
+    let frac = parts.next().unwrap_or("");
+    if frac.len() > 3 || !frac.bytes().all(|chr| chr.is_ascii_digit()) {
+        return None;
+    }
+    let frac = frac.bytes().chain("000".bytes()).take(3)
+        .fold(0u16, |frac, chr| frac * 10 + u16::from(chr - b'0'));
+    if int + frac > 1000 {
+        return None;
+    }
+    Some(int + frac)
+}
+
+// splits header by commas which is not inside quoted strings
+fn split_list(src: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (pos, chr) in src.char_indices() {
+        match chr {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&src[start..pos]);
+                start = pos + 1;
+            },
+            _ => (),
+        }
+    }
+    items.push(&src[start..]);
+    items.into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+impl<'a> MediaRange<'a> {
+    pub fn parse(src: &'a str) -> Option<Self> {
+        let content_type = ContentType::parse(src).ok()?;
+        let quality = match content_type.get_param("q") {
+            Some(quality) => parse_quality(quality)?,
+            None => 1000,
+        };
+        // the media range must have a type and a subtype
+        if content_type.num_subtypes() == 0 {
+            return None;
+        }
+        Some(MediaRange { content_type, quality })
+    }
+
+    pub fn content_type(&self) -> &ContentType<'a> {
+        &self.content_type
+    }
+
+    pub fn quality(&self) -> u16 {
+        self.quality
+    }
+
+    // the params before quality belong to media type,
+    // the rest is accept extensions
+    fn media_params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.content_type.iter_params()
+            .take_while(|(name, _)| !name.eq_ignore_ascii_case("q"))
+    }
+
+    // */* is less specific than type/* which is less specific
+    // than type/*+suffix which is less specific than type/subtype,
+    // the range with more params is more specific than the same range
+    pub fn specificity(&self) -> usize {
+        let level = if self.content_type.get_type() == "*" {
+            0
+        } else if self.content_type.get_subtype(0) != Some("*") {
+            3
+        } else if self.content_type.num_subtypes() == 1 {
+            1
+        } else {
+            2
+        };
+        level * 16 + cmp::min(self.media_params().count(), 15)
+    }
+
+    // the params of range must be carried by the content type
+    pub fn matches(&self, content_type: &ContentType) -> bool {
+        content_type.matches(&self.content_type) &&
+            self.media_params().all(|(name, value)| content_type.get_param(name)
+                                    .map(|param| param.eq_ignore_ascii_case(value))
+                                    .unwrap_or(false))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Accept<'a> {
+    ranges: Vec<MediaRange<'a>>,
+}
+
+impl<'a> Accept<'a> {
+    // malformed ranges are ignored
+    pub fn parse(src: &'a str) -> Self {
+        let mut ranges: Vec<MediaRange> = split_list(src).into_iter()
+            .filter_map(MediaRange::parse)
+            .collect();
+        // stable sort keeps the order of client for equal ranges
+        ranges.sort_by(|a, b| match b.quality.cmp(&a.quality) {
+            Ordering::Equal => b.specificity().cmp(&a.specificity()),
+            ordering => ordering,
+        });
+        Accept { ranges }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MediaRange<'a>> {
+        self.ranges.iter()
+    }
+
+    // the most specific matching range determines the quality,
+    // of equally specific ranges the highest quality wins
+    pub fn quality_of(&self, content_type: &ContentType) -> u16 {
+        self.ranges.iter()
+            .filter(|range| range.matches(content_type))
+            .max_by(|a, b| a.specificity().cmp(&b.specificity())
+                    .then(a.quality.cmp(&b.quality)))
+            .map(|range| range.quality)
+            .unwrap_or(0)
+    }
+
+    // offers is ordered by server preference
+    pub fn negotiate<'o>(&self, offers: &[&'o str]) -> Option<&'o str> {
+        let mut best = None;
+        for offer in offers {
+            let quality = match ContentType::parse(*offer) {
+                Ok(content_type) => self.quality_of(&content_type),
+                Err(_) => continue,
+            };
+            if quality > 0 && best.map(|(_, best)| quality > best).unwrap_or(true) {
+                best = Some((*offer, quality));
+            }
+        }
+        best.map(|(offer, _)| offer)
+    }
+}
+
+pub trait NegotiateType: ReadHeader {
+    fn negotiate_type<'o>(&self, offers: &[&'o str]) -> CodecResult<&'o str> {
+        // missing header means that any type is acceptable
+        let accept = self.get_header_str("Accept").unwrap_or("*/*");
+        Accept::parse(accept).negotiate(offers)
+            .ok_or_else(|| CodecError::new(CodecErrorKind::NotAcceptable)
+                        .with_source(format!("Unable to satisfy {}", accept)))
+    }
+}
+
+impl<T> NegotiateType for Request<T> {}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, Response, StatusCode};
+    use bytes::{Bytes};
+    use super::super::{Base64, CodecRegistry, EncodeChain};
+    use super::*;
+
+    #[test]
+    fn test_parse_quality() {
+        assert_eq!(parse_quality("1"), Some(1000));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("0.5"), Some(500));
+        assert_eq!(parse_quality("0.05"), Some(50));
+        assert_eq!(parse_quality("0"), Some(0));
+        assert_eq!(parse_quality("1.5"), None);
+        assert_eq!(parse_quality("0.5555"), None);
+        assert_eq!(parse_quality("abc"), None);
+    }
+
+    #[test]
+    fn test_parse_accept() {
+        let a = Accept::parse("text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.5, text/plain; q=bad, junk");
+        let r: Vec<(String, u16)> = a.iter()
+            .map(|range| (range.content_type().as_ref().into(), range.quality()))
+            .collect();
+
+        assert_eq!(r, vec![("text/html".into(), 1000),
+                           ("text/html".into(), 700),
+                           ("*/*".into(), 500),
+                           ("text/*".into(), 300)]);
+    }
+
+    #[test]
+    fn test_quality_of() {
+        let a = Accept::parse("text/*;q=0.3, text/html;q=0.7, */*;q=0.5");
+
+        assert_eq!(a.quality_of(&ContentType::new("text/html")), 700);
+        assert_eq!(a.quality_of(&ContentType::new("text/plain")), 300);
+        assert_eq!(a.quality_of(&ContentType::new("image/jpeg")), 500);
+    }
+
+    #[test]
+    fn test_quality_of_tie() {
+        let a = Accept::parse("text/html;q=0.2, text/*;q=0.9, text/html;q=0.8");
+
+        assert_eq!(a.quality_of(&ContentType::new("text/html")), 800);
+
+        let a = Accept::parse("application/*+json;q=0.4, application/*+cbor;q=0.3, application/*+json;q=0.1");
+
+        assert_eq!(a.quality_of(&ContentType::new("application/problem+json")), 400);
+    }
+
+    #[test]
+    fn test_quality_of_params() {
+        let a = Accept::parse("text/html;level=1, text/html;q=0.1, text/*;level=2;q=0.5");
+
+        assert_eq!(a.quality_of(&ContentType::new("text/html")), 100);
+        assert_eq!(a.quality_of(&ContentType::new("text/html;level=1")), 1000);
+        assert_eq!(a.quality_of(&ContentType::new("text/html;level=2")), 100);
+        assert_eq!(a.quality_of(&ContentType::new("text/plain;level=2")), 500);
+        assert_eq!(a.quality_of(&ContentType::new("text/plain")), 0);
+        assert_eq!(a.negotiate(&["text/html", "text/html;level=1"]), Some("text/html;level=1"));
+    }
+
+    #[test]
+    fn test_quality_of_suffix() {
+        let a = Accept::parse("application/*;q=0.2, application/*+json;q=0.6");
+
+        assert_eq!(a.quality_of(&ContentType::new("application/problem+json")), 600);
+        assert_eq!(a.quality_of(&ContentType::new("application/cbor")), 200);
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let a = Accept::parse("application/json+base64, application/cbor;q=0.9, */*;q=0.1");
+
+        assert_eq!(a.negotiate(&["application/json", "application/cbor"]), Some("application/cbor"));
+        assert_eq!(a.negotiate(&["application/json", "application/json+base64"]), Some("application/json+base64"));
+        assert_eq!(a.negotiate(&["text/plain"]), Some("text/plain"));
+        assert_eq!(Accept::parse("application/json;q=0").negotiate(&["application/json"]), None);
+    }
+
+    #[test]
+    fn test_negotiate_type() {
+        let r = Request::builder()
+            .header("Accept", "application/cbor;q=0.5, application/json+base64")
+            .body(())
+            .unwrap();
+        let t = r.negotiate_type(&["application/json", "application/cbor", "application/json+base64"]).unwrap();
+
+        assert_eq!(t, "application/json+base64");
+
+        let a = Response::builder()
+            .body(vec![13u8, 1, 0])
+            .unwrap();
+        let d: Response<Bytes> = a.encode_chain(&CodecRegistry::new().with_codec(Base64::Standard), t).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/json+base64"));
+        assert_eq!(d.into_body(), "WzEzLDEsMF0=");
+    }
+
+    #[test]
+    fn test_negotiate_type_no_header() {
+        let r = Request::builder()
+            .body(())
+            .unwrap();
+
+        assert_eq!(r.negotiate_type(&["application/cbor", "application/json"]).unwrap(), "application/cbor");
+    }
+
+    #[test]
+    fn test_negotiate_type_err() {
+        let r = Request::builder()
+            .header("Accept", "text/html")
+            .body(())
+            .unwrap();
+        let e = r.negotiate_type(&["application/json"]).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::NotAcceptable);
+        assert_eq!(e.status(), StatusCode::NOT_ACCEPTABLE);
+    }
+}
@@ -1,140 +1,158 @@
-use http::{Request, Response};
-use http::header::{HeaderValue};
 use bytes::{Bytes};
 use base64lib;
-use super::{ReadHeader, WriteHeader, CodecError, CodecResult, UnwrapType, WrapType};
+use super::{WriteHeader, AsTypePattern, ContentType, CodecError, CodecPosition, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Base64 {
+    #[default]
+    Standard,
+    StandardNoPad,
+    UrlSafe,
+    UrlSafeNoPad,
+}
 
-pub type Base64Result<T> = Result<T, base64lib::DecodeError>;
+impl Base64 {
+    pub fn from_subtype(subtype: &str) -> Option<Self> {
+        match subtype {
+            "base64" => Some(Base64::Standard),
+            "base64nopad" => Some(Base64::StandardNoPad),
+            "base64url" => Some(Base64::UrlSafe),
+            "base64urlnopad" => Some(Base64::UrlSafeNoPad),
+            _ => None,
+        }
+    }
 
-pub trait DecodeBase64: Sized {
-    fn decode_base64_native(self) -> Base64Result<Self>;
-    
-    #[inline]
-    fn decode_base64(self) -> CodecResult<Self> {
-        self.decode_base64_native().map_err(|_| CodecError::InvalidData)
-    }   
-}
+    pub fn from_content_type(mimetype: &str) -> Option<Self> {
+        ContentType::parse(mimetype).ok()
+            .and_then(|ct| ct.last_subtype().and_then(Base64::from_subtype))
+    }
 
-pub trait DecodeBase64Body: DecodeBase64 + ReadHeader + WriteHeader + UnwrapType
-{
-    #[inline]
-    fn decode_base64_with_type<V>(self, mimetype: V) -> CodecResult<Self>
-        where HeaderValue: PartialEq<V>
-    {
-        if self.is_header("Content-Type", mimetype) {
-            self.decode_base64()
-        } else {
-            Err(CodecError::InvalidType)
+    pub fn config(&self) -> base64lib::Config {
+        match self {
+            Base64::Standard => base64lib::STANDARD,
+            Base64::StandardNoPad => base64lib::STANDARD_NO_PAD,
+            Base64::UrlSafe => base64lib::URL_SAFE,
+            Base64::UrlSafeNoPad => base64lib::URL_SAFE_NO_PAD,
         }
     }
+}
 
-    #[inline]
-    fn decode_base64_auto_type(self) -> CodecResult<Self> {
-        if let Some(mimetype) = self.unwrap_type("base64") {
-            self.decode_base64().map(move |mut new_self| {
-                new_self.set_header("Content-Type", mimetype);
-                new_self
-            })
-        } else {
-            Err(CodecError::InvalidType)
+impl BodyCodec for Base64 {
+    fn subtype(&self) -> &str {
+        match self {
+            Base64::Standard => "base64",
+            Base64::StandardNoPad => "base64nopad",
+            Base64::UrlSafe => "base64url",
+            Base64::UrlSafeNoPad => "base64urlnopad",
         }
     }
 }
 
-impl DecodeBase64 for Bytes {
-    fn decode_base64_native(self) -> Base64Result<Bytes> {
-        Ok(base64lib::decode(&self)?.into())
+impl BodyDecoder<Bytes> for Base64 {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        base64lib::decode_config(&data, self.config()).map(Bytes::from).map_err(|error| {
+            let codec_error = CodecError::invalid_data(self.subtype());
+            match error {
+                base64lib::DecodeError::InvalidByte(offset, _) =>
+                    codec_error.with_position(CodecPosition::Offset(offset)),
+                _ => codec_error,
+            }.with_source(error)
+        })
     }
 }
 
-impl DecodeBase64 for Request<Bytes>
-{
-    fn decode_base64_native(self) -> Base64Result<Request<Bytes>> {
-        let (parts, body) = self.into_parts();
-        let body = body.decode_base64_native()?;
-        Ok(Request::from_parts(parts, body))
+impl BodyEncoder<Bytes> for Base64 {
+    fn encode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        Ok(base64lib::encode_config(&data, self.config()).into())
     }
 }
 
-impl DecodeBase64Body for Request<Bytes> {}
+pub trait DecodeBase64: Sized {
+    fn decode_base64(self) -> CodecResult<Self>;
+}
 
-impl DecodeBase64 for Response<Bytes>
+impl<B> DecodeBase64 for B
+    where B: DecodeData<B, Data = Bytes>
 {
-    fn decode_base64_native(self) -> Base64Result<Response<Bytes>> {
-        let (parts, body) = self.into_parts();
-        let body = body.decode_base64_native()?;
-        Ok(Response::from_parts(parts, Bytes::from(body)))
+    #[inline]
+    fn decode_base64(self) -> CodecResult<Self> {
+        self.decode_data(&Base64::Standard)
     }
 }
 
-impl DecodeBase64Body for Response<Bytes> {}
-
-pub trait EncodeBase64: Sized {
-    fn encode_base64_native(self) -> Base64Result<Self>;
+pub trait DecodeBase64Body: DecodeBase64 + WriteHeader
+{
+    fn decode_base64_with_type<V>(self, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern;
 
-    #[inline]
-    fn encode_base64(self) -> CodecResult<Self> {
-        self.encode_base64_native().map_err(|_| CodecError::InvalidData)
-    }
+    fn decode_base64_auto_type(self) -> CodecResult<Self>;
 }
 
-pub trait EncodeBase64Body: EncodeBase64 + ReadHeader + WriteHeader + WrapType
+impl<B> DecodeBase64Body for B
+    where B: DecodeBody<B, Data = Bytes> + WriteHeader
 {
     #[inline]
-    fn encode_base64_with_type(self, mimetype: &'static str) -> CodecResult<Self>
+    fn decode_base64_with_type<V>(self, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern
     {
-        self.encode_base64()
-            .map(|mut new_self| {
-                new_self.set_header("Content-Type", HeaderValue::from_static(mimetype));
-                new_self
-            })
+        self.decode_body_with_type(&Base64::Standard, mimetype)
     }
 
     #[inline]
-    fn encode_base64_auto_type(self) -> CodecResult<Self> {
-        if let Some(mimetype) = self.wrap_type("base64") {
-            self.encode_base64()
-                .map(move |mut new_self| {
-                    new_self.set_header("Content-Type", mimetype);
-                    new_self
-                })
-        } else {
-            Err(CodecError::InvalidType)
-        }
+    fn decode_base64_auto_type(self) -> CodecResult<Self> {
+        // the variant is selected by subtype
+        let variant = self.get_header_str("Content-Type")
+            .and_then(Base64::from_content_type)
+            .unwrap_or_default();
+        self.decode_body_auto_type(&variant)
     }
 }
 
-impl EncodeBase64 for Bytes {
-    fn encode_base64_native(self) -> Base64Result<Bytes> {
-        Ok(base64lib::encode(&self).into())
-    }
+pub trait EncodeBase64: Sized {
+    fn encode_base64(self) -> CodecResult<Self>;
 }
 
-impl EncodeBase64 for Request<Bytes>
+impl<B> EncodeBase64 for B
+    where B: EncodeData<B, Data = Bytes>
 {
-    fn encode_base64_native(self) -> Base64Result<Request<Bytes>> {
-        let (parts, body) = self.into_parts();
-        let body = body.encode_base64_native()?;
-        Ok(Request::from_parts(parts, body))
+    #[inline]
+    fn encode_base64(self) -> CodecResult<Self> {
+        self.encode_data(&Base64::Standard)
     }
 }
 
-impl EncodeBase64Body for Request<Bytes> {}
+pub trait EncodeBase64Body: EncodeBase64 + WriteHeader
+{
+    fn encode_base64_with_type(self, mimetype: &'static str) -> CodecResult<Self>;
+
+    fn encode_base64_auto_type(self) -> CodecResult<Self>;
 
-impl EncodeBase64 for Response<Bytes>
+    fn encode_base64_variant_auto_type(self, codec: Base64) -> CodecResult<Self>;
+}
+
+impl<B> EncodeBase64Body for B
+    where B: EncodeBody<B, Data = Bytes> + WriteHeader
 {
-    fn encode_base64_native(self) -> Base64Result<Response<Bytes>> {
-        let (parts, body) = self.into_parts();
-        let body = body.encode_base64_native()?;
-        Ok(Response::from_parts(parts, body))
+    #[inline]
+    fn encode_base64_with_type(self, mimetype: &'static str) -> CodecResult<Self> {
+        self.encode_body_with_type(&Base64::Standard, mimetype)
     }
-}
 
-impl EncodeBase64Body for Response<Bytes> {}
+    #[inline]
+    fn encode_base64_auto_type(self) -> CodecResult<Self> {
+        self.encode_body_auto_type(&Base64::Standard)
+    }
+
+    #[inline]
+    fn encode_base64_variant_auto_type(self, codec: Base64) -> CodecResult<Self> {
+        self.encode_body_auto_type(&codec)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use http::{Request, Response};
+    use super::super::{ReadHeader, CodecErrorKind};
     use super::*;
 
     #[test]
@@ -154,7 +172,7 @@ mod tests {
             .unwrap();
         let d = a.decode_base64();
         
-        assert_eq!(d.unwrap_err(), CodecError::InvalidData);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
     }
 
     #[test]
@@ -176,7 +194,7 @@ mod tests {
             .unwrap();
         let d = a.decode_base64_with_type("application/base64");
         
-        assert_eq!(d.unwrap_err(), CodecError::InvalidData);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
     }
 
     #[test]
@@ -187,7 +205,7 @@ mod tests {
             .unwrap();
         let d = a.decode_base64_with_type("application/base64");
         
-        assert_eq!(d.unwrap_err(), CodecError::InvalidType);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
     }
 
     #[test]
@@ -209,7 +227,7 @@ mod tests {
             .unwrap();
         let d = a.decode_base64_auto_type();
 
-        assert_eq!(d.unwrap_err(), CodecError::InvalidData);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
     }
 
     #[test]
@@ -220,7 +238,7 @@ mod tests {
             .unwrap();
         let d = a.decode_base64_auto_type();
 
-        assert_eq!(d.unwrap_err(), CodecError::InvalidType);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
     }
 
     #[test]
@@ -255,4 +273,40 @@ mod tests {
         assert!(d.is_header("Content-Type", "application/vnd.literium.v1+base64"));
         assert_eq!(d.into_body(), "aGVsbG8gd29ybGQ=");
     }
+
+    #[test]
+    fn test_decode_base64_auto_type_url_safe() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/octet-stream+base64url")
+            .body("-_8".into())
+            .unwrap();
+        let d = a.decode_base64_auto_type().unwrap();
+        
+        assert!(d.is_header("Content-Type", "application/octet-stream"));
+        assert_eq!(d.into_body(), vec![0xfbu8, 0xff]);
+    }
+
+    #[test]
+    fn test_decode_base64_auto_type_no_pad() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/octet-stream+base64nopad")
+            .body("aGVsbG8gd29ybGQ".into())
+            .unwrap();
+        let d = a.decode_base64_auto_type().unwrap();
+        
+        assert!(d.is_header("Content-Type", "application/octet-stream"));
+        assert_eq!(d.into_body(), "hello world");
+    }
+
+    #[test]
+    fn test_encode_base64_url_safe_no_pad() {
+        let a: Response<Bytes> = Response::builder()
+            .header("Content-Type", "application/octet-stream")
+            .body(vec![0xfbu8, 0xff].into())
+            .unwrap();
+        let d = a.encode_base64_variant_auto_type(Base64::UrlSafeNoPad).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/octet-stream+base64urlnopad"));
+        assert_eq!(d.into_body(), "-_8");
+    }
 }
@@ -0,0 +1,257 @@
+use std::error::{Error};
+use futures::{Stream, Poll, Async};
+use http::{Request, Response};
+use http::request::{Parts as RequestParts};
+use http::response::{Parts as ResponseParts};
+use http::header::{HeaderValue, CONTENT_LENGTH};
+use bytes::{Bytes, BytesMut};
+use hyper::{Body};
+use super::{ReadHeader, WriteHeader, AsTypePattern, MatchType, UnwrapType, WrapType, UnwrapBody, WrapBody,
+            CodecError, CodecPosition, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, Base64};
+
+pub type StreamError = Box<dyn Error + Send + Sync>;
+
+pub struct Base64Stream<S> {
+    stream: S,
+    codec: Base64,
+    encode: bool,
+    buffer: BytesMut,
+    offset: usize,
+    done: bool,
+}
+
+impl<S> Base64Stream<S> {
+    pub fn decode(stream: S, codec: Base64) -> Self {
+        Self { stream, codec, encode: false, buffer: BytesMut::new(), offset: 0, done: false }
+    }
+
+    pub fn encode(stream: S, codec: Base64) -> Self {
+        Self { stream, codec, encode: true, buffer: BytesMut::new(), offset: 0, done: false }
+    }
+
+    fn process(&mut self, data: Bytes) -> CodecResult<Bytes> {
+        let offset = self.offset;
+        self.offset += data.len();
+        if self.encode {
+            self.codec.encode_bytes(data)
+        } else {
+            // make error position relative to the whole stream
+            self.codec.decode_bytes(data).map_err(|error| match error.position() {
+                Some(CodecPosition::Offset(position)) =>
+                    error.with_position(CodecPosition::Offset(offset + position)),
+                _ => error,
+            })
+        }
+    }
+}
+
+impl<S> Stream for Base64Stream<S>
+    where S: Stream,
+          S::Item: AsRef<[u8]>,
+          S::Error: Into<StreamError>
+{
+    type Item = Bytes;
+    type Error = StreamError;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, StreamError> {
+        // 3 bytes of data is encoded to 4 bytes of base64
+        let block = if self.encode { 3 } else { 4 };
+        loop {
+            if self.done {
+                return Ok(Async::Ready(None));
+            }
+            match self.stream.poll().map_err(Into::into)? {
+                Async::NotReady => return Ok(Async::NotReady),
+                Async::Ready(Some(chunk)) => {
+                    self.buffer.extend_from_slice(chunk.as_ref());
+                    let len = self.buffer.len() / block * block;
+                    if len > 0 {
+                        let data = self.buffer.split_to(len).freeze();
+                        return Ok(Async::Ready(Some(self.process(data)?)));
+                    }
+                },
+                Async::Ready(None) => {
+                    self.done = true;
+                    if !self.buffer.is_empty() {
+                        let data = self.buffer.take().freeze();
+                        return Ok(Async::Ready(Some(self.process(data)?)));
+                    }
+                },
+            }
+        }
+    }
+}
+
+fn map_body<T, H, F>(this: T, f: F) -> T
+    where T: UnwrapBody<H, Body> + WrapBody<H, Body> + WriteHeader,
+          F: FnOnce(Body) -> Body
+{
+    let (parts, body) = this.unwrap_body();
+    let mut this = T::wrap_body(parts, f(body));
+    // length of transformed body is unknown
+    this.remove_header(CONTENT_LENGTH);
+    this
+}
+
+pub trait DecodeBase64Stream<H>: UnwrapBody<H, Body> + WrapBody<H, Body> + ReadHeader + WriteHeader + MatchType + UnwrapType + Sized
+{
+    #[inline]
+    fn decode_base64_stream(self) -> Self {
+        self.decode_base64_stream_variant(Base64::Standard)
+    }
+
+    #[inline]
+    fn decode_base64_stream_variant(self, codec: Base64) -> Self {
+        map_body(self, |body| Body::wrap_stream(Base64Stream::decode(body, codec)))
+    }
+
+    #[inline]
+    fn decode_base64_stream_with_type<V>(self, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern
+    {
+        if mimetype.as_type_pattern().map(|pattern| self.match_type(pattern)).unwrap_or(false) {
+            Ok(self.decode_base64_stream())
+        } else {
+            Err(CodecError::invalid_type(Base64::Standard.subtype()))
+        }
+    }
+
+    #[inline]
+    fn decode_base64_stream_auto_type(self) -> CodecResult<Self> {
+        let codec = self.get_header_str("Content-Type")
+            .and_then(Base64::from_content_type)
+            .unwrap_or_default();
+        let mimetype = self.unwrap_type(codec.subtype())
+            .map_err(|error| CodecError::invalid_type(codec.subtype()).with_source(error))?
+            .ok_or_else(|| CodecError::invalid_type(codec.subtype()))?;
+        let mut new_self = self.decode_base64_stream_variant(codec);
+        new_self.set_header("Content-Type", mimetype);
+        Ok(new_self)
+    }
+}
+
+impl DecodeBase64Stream<RequestParts> for Request<Body> {}
+impl DecodeBase64Stream<ResponseParts> for Response<Body> {}
+
+pub trait EncodeBase64Stream<H>: UnwrapBody<H, Body> + WrapBody<H, Body> + ReadHeader + WriteHeader + WrapType + Sized
+{
+    #[inline]
+    fn encode_base64_stream(self) -> Self {
+        self.encode_base64_stream_variant(Base64::Standard)
+    }
+
+    #[inline]
+    fn encode_base64_stream_variant(self, codec: Base64) -> Self {
+        map_body(self, |body| Body::wrap_stream(Base64Stream::encode(body, codec)))
+    }
+
+    #[inline]
+    fn encode_base64_stream_with_type(self, mimetype: &'static str) -> Self {
+        let mut new_self = self.encode_base64_stream();
+        new_self.set_header("Content-Type", HeaderValue::from_static(mimetype));
+        new_self
+    }
+
+    #[inline]
+    fn encode_base64_stream_auto_type(self) -> CodecResult<Self> {
+        self.encode_base64_stream_variant_auto_type(Base64::Standard)
+    }
+
+    #[inline]
+    fn encode_base64_stream_variant_auto_type(self, codec: Base64) -> CodecResult<Self> {
+        let mimetype = self.wrap_type(codec.subtype())
+            .map_err(|error| CodecError::invalid_type(codec.subtype()).with_source(error))?
+            .ok_or_else(|| CodecError::invalid_type(codec.subtype()))?;
+        let mut new_self = self.encode_base64_stream_variant(codec);
+        new_self.set_header("Content-Type", mimetype);
+        Ok(new_self)
+    }
+}
+
+impl EncodeBase64Stream<RequestParts> for Request<Body> {}
+impl EncodeBase64Stream<ResponseParts> for Response<Body> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error};
+    use futures::{Future};
+    use futures::stream::{iter_ok};
+    use http::{Request, Response};
+    use super::super::{CodecErrorKind};
+    use super::*;
+
+    fn body(chunks: Vec<&'static str>) -> Body {
+        Body::wrap_stream(iter_ok::<_, Error>(chunks))
+    }
+
+    #[test]
+    fn test_decode_base64_stream() {
+        let a = Request::builder()
+            .header("Content-Type", "text/plain+base64")
+            .header("Content-Length", "16")
+            .body(body(vec!["aGV", "sbG8gd2", "9ybG", "Q="]))
+            .unwrap();
+        let d = a.decode_base64_stream_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "text/plain"));
+        assert!(d.get_header("Content-Length").is_none());
+
+        let b = d.into_body().concat2().wait().unwrap();
+
+        assert_eq!(b.into_bytes(), "hello world");
+    }
+
+    #[test]
+    fn test_decode_base64_stream_err_data() {
+        let a = Request::builder()
+            .body(body(vec!["aGVs", "bG8g", "d2*y", "bGQ="]))
+            .unwrap();
+        let d = a.decode_base64_stream();
+        let e = d.into_body().concat2().wait().unwrap_err();
+        let e = e.into_cause().unwrap().downcast::<CodecError>().unwrap();
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidData);
+        assert_eq!(e.position(), Some(CodecPosition::Offset(10)));
+    }
+
+    #[test]
+    fn test_decode_base64_stream_err_type() {
+        let a = Request::builder()
+            .header("Content-Type", "text/plain+json")
+            .body(body(vec!["aGVsbG8gd29ybGQ="]))
+            .unwrap();
+        let d = a.decode_base64_stream_auto_type();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_base64_stream() {
+        let a = Response::builder()
+            .header("Content-Type", "text/plain")
+            .body(body(vec!["h", "ell", "o w", "orl", "d"]))
+            .unwrap();
+        let d = a.encode_base64_stream_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "text/plain+base64"));
+
+        let b = d.into_body().concat2().wait().unwrap();
+
+        assert_eq!(b.into_bytes(), "aGVsbG8gd29ybGQ=");
+    }
+
+    #[test]
+    fn test_encode_base64_stream_url_safe_no_pad() {
+        let a = Response::builder()
+            .header("Content-Type", "application/octet-stream")
+            .body(Body::from(vec![0xfbu8, 0xff]))
+            .unwrap();
+        let d = a.encode_base64_stream_variant_auto_type(Base64::UrlSafeNoPad).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/octet-stream+base64urlnopad"));
+
+        let b = d.into_body().concat2().wait().unwrap();
+
+        assert_eq!(b.into_bytes(), "-_8");
+    }
+}
@@ -1,6 +1,7 @@
 use bytes::{Bytes};
 use super::{PublicKey, SecretKey, Key};
 
+#[allow(clippy::wrong_self_convention)]
 pub trait IntoBinary {
     fn into_binary(&self) -> &[u8];
 }
@@ -65,6 +66,6 @@ impl IntoBinary for Key {
 
 impl FromBinary for Key {
     fn from_binary(b: &[u8]) -> Option<Self> {
-        Key::from_slice(b.as_ref())
+        Key::from_slice(b)
     }
 }
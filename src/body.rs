@@ -1,9 +1,11 @@
-use futures::{Future, Stream};
+use std::{fmt, error};
+use futures::{Future, Stream, future};
 use http::{Request, Response};
 use http::request::{Parts as RequestParts};
 use http::response::{Parts as ResponseParts};
-use bytes::{Bytes};
+use bytes::{Bytes, BytesMut};
 use hyper::{Error, Body};
+use super::{ReadHeader};
 
 pub trait UnwrapBody<H, B>
 {
@@ -39,18 +41,65 @@ impl<T> WrapBody<ResponseParts, T> for Response<T> {
     }
 }
 
-pub type BodyFuture<T> = Box<Future<Item = T, Error = Error>>;
+#[derive(Debug)]
+pub enum BodyError {
+    Transport(Error),
+    TooLarge,
+}
+
+impl From<Error> for BodyError {
+    fn from(error: Error) -> Self {
+        BodyError::Transport(error)
+    }
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BodyError::Transport(error) => error.fmt(f),
+            BodyError::TooLarge => f.write_str("Body too large"),
+        }
+    }
+}
+
+impl error::Error for BodyError {}
 
-pub trait ConcatBody<T, H>: UnwrapBody<H, Body> + Sized
-    where T: WrapBody<H, Bytes>,
-          H: 'static
+pub type BodyFuture<T> = Box<dyn Future<Item = T, Error = BodyError> + Send>;
+
+pub trait ConcatBody<T, H>: UnwrapBody<H, Body> + ReadHeader + Sized
+    where T: WrapBody<H, Bytes> + Send + 'static,
+          H: Send + 'static
 {
-    fn concat_body_limited(self, _maxlen: Option<usize>) -> BodyFuture<T>
+    fn concat_body_limited(self, maxlen: Option<usize>) -> BodyFuture<T>
     {
+        if let Some(maxlen) = maxlen {
+            // reject early when declared length already exceeds limit
+            if self.get_header_str("Content-Length")
+                .and_then(|val| val.parse::<usize>().ok())
+                .map(|len| len > maxlen)
+                .unwrap_or(false) {
+                    return Box::new(future::err(BodyError::TooLarge));
+                }
+        }
+        
         let (parts, body) = self.unwrap_body();
-        Box::new(body.concat2().map(move |body| {
-            T::wrap_body(parts, body.into_bytes())
-        }))
+        
+        if let Some(maxlen) = maxlen {
+            Box::new(body.map_err(BodyError::from).fold(BytesMut::new(), move |mut data, chunk| {
+                if data.len() + chunk.len() > maxlen {
+                    Err(BodyError::TooLarge)
+                } else {
+                    data.extend_from_slice(&chunk);
+                    Ok(data)
+                }
+            }).map(move |data| {
+                T::wrap_body(parts, data.freeze())
+            }))
+        } else {
+            Box::new(body.concat2().map_err(BodyError::from).map(move |body| {
+                T::wrap_body(parts, body.into_bytes())
+            }))
+        }
     }
     
     fn concat_body(self) -> BodyFuture<T> {
@@ -98,6 +147,51 @@ mod tests {
         assert_eq!(d.into_body(), "hello world");
     }
 
+    #[test]
+    fn test_concat_body_with_limit_ok() {
+        let cs = vec!["hello", " ", "world"];
+        let s = iter_ok::<_, Error>(cs);
+        let b = Body::wrap_stream(s);
+        
+        let a = Request::builder()
+            .body(b)
+            .unwrap();
+        let d = a.concat_body_with_limit(11).wait().unwrap();
+        
+        assert_eq!(d.into_body(), "hello world");
+    }
+
+    #[test]
+    fn test_concat_body_with_limit_err_stream() {
+        let cs = vec!["hello", " ", "world"];
+        let s = iter_ok::<_, Error>(cs);
+        let b = Body::wrap_stream(s);
+        
+        let a = Request::builder()
+            .body(b)
+            .unwrap();
+        let d = a.concat_body_with_limit(10).wait();
+        
+        match d {
+            Err(BodyError::TooLarge) => (),
+            _ => panic!("expected TooLarge"),
+        }
+    }
+
+    #[test]
+    fn test_concat_body_with_limit_err_length() {
+        let a = Request::builder()
+            .header("Content-Length", "1048576")
+            .body(Body::from("hello world"))
+            .unwrap();
+        let d = a.concat_body_with_limit(1024).wait();
+        
+        match d {
+            Err(BodyError::TooLarge) => (),
+            _ => panic!("expected TooLarge"),
+        }
+    }
+
     #[test]
     fn test_rollup_body() {
         let a: Response<Bytes> = Response::builder()
@@ -0,0 +1,216 @@
+use bytes::{Bytes};
+use serde::{ser, de};
+use serde_cbor as cbor;
+use super::{WriteHeader, AsTypePattern, CodecError, CodecPosition, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cbor;
+
+impl BodyCodec for Cbor {
+    fn subtype(&self) -> &str {
+        "cbor"
+    }
+}
+
+impl<T> BodyDecoder<T> for Cbor
+    where for<'de> T: de::Deserialize<'de>
+{
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<T> {
+        cbor::from_slice(&data).map_err(|error| {
+            // value errors may hide syntax errors which follows
+            let error = if error.is_data() {
+                cbor::from_slice::<de::IgnoredAny>(&data).err().unwrap_or(error)
+            } else {
+                error
+            };
+            let position = CodecPosition::Offset(error.offset() as usize);
+            if error.is_data() {
+                CodecError::invalid_value(self.subtype())
+            } else {
+                CodecError::invalid_data(self.subtype())
+            }.with_position(position).with_source(error)
+        })
+    }
+}
+
+impl<T> BodyEncoder<T> for Cbor
+    where T: ser::Serialize
+{
+    fn encode_bytes(&self, data: T) -> CodecResult<Bytes> {
+        cbor::to_vec(&data).map(Bytes::from)
+            .map_err(|error| CodecError::encode_failed(self.subtype()).with_source(error))
+    }
+}
+
+pub trait DecodeCbor<T>: Sized {
+    fn decode_cbor(self) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeCbor<T> for B
+    where B: DecodeData<T>,
+          for<'de> B::Data: de::Deserialize<'de>
+{
+    #[inline]
+    fn decode_cbor(self) -> CodecResult<T> {
+        self.decode_data(&Cbor)
+    }
+}
+
+pub trait DecodeCborBody<T>: DecodeCbor<T>
+    where T: WriteHeader
+{
+    fn decode_cbor_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern;
+
+    fn decode_cbor_auto_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeCborBody<T> for B
+    where B: DecodeBody<T>,
+          T: WriteHeader,
+          for<'de> B::Data: de::Deserialize<'de>
+{
+    #[inline]
+    fn decode_cbor_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern
+    {
+        self.decode_body_with_type(&Cbor, mimetype)
+    }
+
+    #[inline]
+    fn decode_cbor_auto_type(self) -> CodecResult<T> {
+        self.decode_body_auto_type(&Cbor)
+    }
+}
+
+pub trait EncodeCbor<T>: Sized {
+    fn encode_cbor(self) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeCbor<T> for B
+    where B: EncodeData<T>,
+          B::Data: ser::Serialize
+{
+    #[inline]
+    fn encode_cbor(self) -> CodecResult<T> {
+        self.encode_data(&Cbor)
+    }
+}
+
+pub trait EncodeCborBody<T>: EncodeCbor<T>
+    where T: WriteHeader
+{
+    fn encode_cbor_with_type(self, mimetype: &'static str) -> CodecResult<T>;
+
+    fn encode_cbor_auto_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeCborBody<T> for B
+    where B: EncodeBody<T>,
+          T: WriteHeader,
+          B::Data: ser::Serialize
+{
+    #[inline]
+    fn encode_cbor_with_type(self, mimetype: &'static str) -> CodecResult<T> {
+        self.encode_body_with_type(&Cbor, mimetype)
+    }
+
+    #[inline]
+    fn encode_cbor_auto_type(self) -> CodecResult<T> {
+        self.encode_body_auto_type(&Cbor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, Response};
+    use super::super::{ReadHeader, CodecErrorKind};
+    use super::*;
+
+    #[test]
+    fn test_decode_cbor_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .body(vec![0x83u8, 13, 1, 0].into())
+            .unwrap();
+        let d: Request<Vec<u8>> = a.decode_cbor().unwrap();
+        
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
+    #[test]
+    fn test_decode_cbor_err_data() {
+        let a: Request<Bytes> = Request::builder()
+            .body(vec![0x83u8, 13, 1].into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_cbor();
+        
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_cbor_with_type_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/cbor")
+            .body(vec![0x83u8, 13, 1, 0].into())
+            .unwrap();
+        let d: Request<Vec<u8>> = a.decode_cbor_with_type("application/cbor").unwrap();
+        
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
+    #[test]
+    fn test_decode_cbor_with_type_err_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/json")
+            .body(vec![0x83u8, 13, 1, 0].into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_cbor_with_type("application/cbor");
+        
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_decode_cbor_auto_type_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+cbor")
+            .body(vec![0x83u8, 13, 1, 0].into())
+            .unwrap();
+        let d: Request<Vec<u8>> = a.decode_cbor_auto_type().unwrap();
+        
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1"));
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
+    #[test]
+    fn test_decode_cbor_auto_type_err_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+json")
+            .body(vec![0x83u8, 13, 1, 0].into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_cbor_auto_type();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_cbor() {
+        let a = Response::builder()
+            .body(vec![13u8, 1, 0])
+            .unwrap();
+        let d: Response<Bytes> = a.encode_cbor().unwrap();
+        
+        assert_eq!(d.into_body(), vec![0x83u8, 13, 1, 0]);
+    }
+
+    #[test]
+    fn test_encode_cbor_auto_type() {
+        let a = Response::builder()
+            .header("Content-Type", "application/vnd.literium.v1")
+            .body(vec![13u8, 1, 0])
+            .unwrap();
+        let d: Response<Bytes> = a.encode_cbor_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1+cbor"));
+        assert_eq!(d.into_body(), vec![0x83u8, 13, 1, 0]);
+    }
+}
@@ -0,0 +1,194 @@
+use http::{HttpTryFrom};
+use http::header::{HeaderValue};
+use bytes::{Bytes};
+use super::{ReadHeader, WriteHeader, CodecError, CodecErrorKind, CodecResult, CodecRegistry, ContentType,
+            DecodeBody, EncodeBody, DecodeJsonBody, EncodeJsonBody, DecodeCborBody, EncodeCborBody, OpenBoxChain};
+
+fn last_subtype<T: ReadHeader>(this: &T) -> Option<String> {
+    this.get_header_str("Content-Type")
+        .and_then(|mimetype| ContentType::parse(mimetype).ok())
+        .and_then(|ct| ct.last_subtype().map(String::from))
+}
+
+pub trait DecodeChain<T>: Sized {
+    fn decode_chain(self, registry: &CodecRegistry) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeChain<T> for B
+    where B: DecodeJsonBody<T> + DecodeCborBody<T> + DecodeBody<B, Data = Bytes> + OpenBoxChain + WriteHeader,
+          T: WriteHeader
+{
+    fn decode_chain(self, registry: &CodecRegistry) -> CodecResult<T> {
+        let mut this = self;
+        loop {
+            let subtype = last_subtype(&this)
+                .ok_or_else(|| CodecError::new(CodecErrorKind::InvalidType))?;
+            this = match subtype.as_str() {
+                "json" => return this.decode_json_auto_type(),
+                "cbor" => return this.decode_cbor_auto_type(),
+                "box" => this.open_box_chain(
+                    registry.get_cryptobox()
+                        .ok_or_else(|| CodecError::invalid_type("box"))?)?,
+                subtype => this.decode_body_auto_type(
+                    registry.get_codec(subtype)
+                        .ok_or_else(|| CodecError::invalid_type(subtype))?)?,
+            };
+        }
+    }
+}
+
+pub trait EncodeChain<T>: Sized {
+    fn encode_chain(self, registry: &CodecRegistry, mimetype: &str) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeChain<T> for B
+    where B: EncodeJsonBody<T> + EncodeCborBody<T> + WriteHeader,
+          T: EncodeBody<T, Data = Bytes> + WriteHeader
+{
+    fn encode_chain(mut self, registry: &CodecRegistry, mimetype: &str) -> CodecResult<T> {
+        let ct = ContentType::parse(mimetype)
+            .map_err(|error| CodecError::new(CodecErrorKind::InvalidType).with_source(error))?;
+        let subtypes: Vec<&str> = ct.iter_subtypes().collect();
+
+        // the data format splits base type and transport encodings
+        let format = subtypes.iter()
+            .position(|subtype| *subtype == "json" || *subtype == "cbor")
+            .ok_or_else(|| CodecError::new(CodecErrorKind::InvalidType))?;
+
+        let mut base = ct.clone();
+        for _ in format..subtypes.len() {
+            base.pop_subtype();
+        }
+        let base = HeaderValue::try_from(base.to_string().as_str())
+            .map_err(|error| CodecError::new(CodecErrorKind::InvalidType).with_source(error))?;
+        self.set_header("Content-Type", base);
+
+        let mut this = match subtypes[format] {
+            "json" => self.encode_json_auto_type()?,
+            _ => self.encode_cbor_auto_type()?,
+        };
+
+        for subtype in &subtypes[format + 1..] {
+            this = match *subtype {
+                "box" => this.encode_body_auto_type(
+                    registry.get_cryptobox()
+                        .ok_or_else(|| CodecError::invalid_type("box"))?)?,
+                subtype => this.encode_body_auto_type(
+                    registry.get_codec(subtype)
+                        .ok_or_else(|| CodecError::invalid_type(subtype))?)?,
+            };
+        }
+
+        Ok(this)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, Response};
+    use super::super::{gen_keypair, gen_key, Base64, Cryptobox, Sealedbox, Secretbox, ReadPeerKey};
+    use super::*;
+
+    #[test]
+    fn test_decode_chain() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+json+base64")
+            .body("WzEzLDEsMF0=".into())
+            .unwrap();
+        let d: Request<Vec<u8>> = a.decode_chain(&CodecRegistry::new().with_codec(Base64::Standard)).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1"));
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
+    #[test]
+    fn test_decode_chain_err_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+json+gzip")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_chain(&CodecRegistry::new().with_codec(Base64::Standard));
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_decode_chain_err_unregistered() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/json+secretbox")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_chain(&CodecRegistry::new().with_codec(Base64::Standard));
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_chain() {
+        let a = Response::builder()
+            .body(vec![13u8, 1, 0])
+            .unwrap();
+        let d: Response<Bytes> = a.encode_chain(&CodecRegistry::new().with_codec(Base64::Standard), "application/json+base64").unwrap();
+
+        assert!(d.is_header("Content-Type", "application/json+base64"));
+        assert_eq!(d.into_body(), "WzEzLDEsMF0=");
+    }
+
+    #[test]
+    fn test_chain_roundtrip() {
+        let (pk, sk) = gen_keypair();
+        let (peer_pk, peer_sk) = gen_keypair();
+        let registry = CodecRegistry::new()
+            .with_codec(Base64::Standard)
+            .with_codec(Sealedbox::new(pk).with_secret_key(sk))
+            .with_codec(Secretbox::new(gen_key()))
+            .with_cryptobox(Cryptobox::new(peer_sk).with_peer_key(peer_pk));
+        let mimetype = "application/vnd.literium.v1+cbor+secretbox+box+sealedbox+base64";
+
+        let a = Request::builder()
+            .body(vec![13u8, 1, 0])
+            .unwrap();
+        let e: Request<Bytes> = a.encode_chain(&registry, mimetype).unwrap();
+
+        assert!(e.is_header("Content-Type", mimetype));
+
+        let d: Request<Vec<u8>> = e.decode_chain(&registry).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1"));
+        assert_eq!(d.peer_key(), Some(peer_pk));
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
+    #[test]
+    fn test_chain_peer_key() {
+        let (server_pk, server_sk) = gen_keypair();
+        let (alice_pk, alice_sk) = gen_keypair();
+        let (bob_pk, bob_sk) = gen_keypair();
+        let server = CodecRegistry::new()
+            .with_codec(Base64::Standard)
+            .with_cryptobox(Cryptobox::new(server_sk).with_peer_keys(&[alice_pk, bob_pk]));
+        let mimetype = "application/json+box+base64";
+
+        for (peer_pk, peer_sk) in [(alice_pk, alice_sk), (bob_pk, bob_sk)] {
+            let peer = CodecRegistry::new()
+                .with_codec(Base64::Standard)
+                .with_cryptobox(Cryptobox::new(peer_sk).with_peer_key(server_pk));
+            let a = Request::builder()
+                .body(vec![13u8, 1, 0])
+                .unwrap();
+            let e: Request<Bytes> = a.encode_chain(&peer, mimetype).unwrap();
+            let d: Request<Vec<u8>> = e.decode_chain(&server).unwrap();
+
+            assert_eq!(d.peer_key(), Some(peer_pk));
+            assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+        }
+
+        let a = Request::builder()
+            .body(vec![13u8, 1, 0])
+            .unwrap();
+        let e: Request<Bytes> = a.encode_chain(&server, "application/json+base64").unwrap();
+        let d: Request<Vec<u8>> = e.decode_chain(&server).unwrap();
+
+        assert_eq!(d.peer_key(), None);
+    }
+}
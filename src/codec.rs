@@ -1,7 +1,428 @@
+use std::{fmt, error};
+use std::collections::{HashMap};
+use http::{Request, Response, StatusCode};
+use http::header::{HeaderValue};
+use bytes::{Bytes};
+use super::{ReadHeader, WriteHeader, AsTypePattern, MatchType, UnwrapType, WrapType, Cryptobox};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CodecError {
+pub enum CodecErrorKind {
+    // content type is not supported by codec
     InvalidType,
+    // data is malformed
     InvalidData,
+    // data is well-formed but has unexpected value
+    InvalidValue,
+    // none of content types is acceptable by client
+    NotAcceptable,
+    // data cannot be encoded on server side
+    EncodeFailed,
+    // codec lacks the keys or options on server side
+    Misconfigured,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecPosition {
+    Offset(usize),
+    LineColumn(usize, usize),
+}
+
+#[derive(Debug)]
+pub struct CodecError {
+    kind: CodecErrorKind,
+    codec: Option<String>,
+    position: Option<CodecPosition>,
+    source: Option<Box<dyn error::Error + Send + Sync>>,
 }
 
 pub type CodecResult<T> = Result<T, CodecError>;
+
+impl CodecError {
+    pub fn new(kind: CodecErrorKind) -> Self {
+        Self { kind, codec: None, position: None, source: None }
+    }
+
+    pub fn invalid_type(codec: &str) -> Self {
+        Self::new(CodecErrorKind::InvalidType).with_codec(codec)
+    }
+
+    pub fn invalid_data(codec: &str) -> Self {
+        Self::new(CodecErrorKind::InvalidData).with_codec(codec)
+    }
+
+    pub fn invalid_value(codec: &str) -> Self {
+        Self::new(CodecErrorKind::InvalidValue).with_codec(codec)
+    }
+
+    pub fn encode_failed(codec: &str) -> Self {
+        Self::new(CodecErrorKind::EncodeFailed).with_codec(codec)
+    }
+
+    pub fn misconfigured(codec: &str) -> Self {
+        Self::new(CodecErrorKind::Misconfigured).with_codec(codec)
+    }
+
+    pub fn with_codec(self, codec: &str) -> Self {
+        Self { codec: Some(codec.into()), ..self }
+    }
+
+    pub fn with_position(self, position: CodecPosition) -> Self {
+        Self { position: Some(position), ..self }
+    }
+
+    pub fn with_source<E>(self, source: E) -> Self
+        where E: Into<Box<dyn error::Error + Send + Sync>>
+    {
+        Self { source: Some(source.into()), ..self }
+    }
+
+    pub fn kind(&self) -> CodecErrorKind {
+        self.kind
+    }
+
+    pub fn codec(&self) -> Option<&str> {
+        self.codec.as_deref()
+    }
+
+    pub fn position(&self) -> Option<CodecPosition> {
+        self.position
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self.kind {
+            CodecErrorKind::InvalidType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            CodecErrorKind::InvalidData => StatusCode::BAD_REQUEST,
+            CodecErrorKind::InvalidValue => StatusCode::UNPROCESSABLE_ENTITY,
+            CodecErrorKind::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            CodecErrorKind::EncodeFailed | CodecErrorKind::Misconfigured => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<CodecErrorKind> for CodecError {
+    fn from(kind: CodecErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl fmt::Display for CodecErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CodecErrorKind::InvalidType => "Invalid type",
+            CodecErrorKind::InvalidData => "Invalid data",
+            CodecErrorKind::InvalidValue => "Invalid value",
+            CodecErrorKind::NotAcceptable => "Not acceptable",
+            CodecErrorKind::EncodeFailed => "Encode failed",
+            CodecErrorKind::Misconfigured => "Misconfigured",
+        })
+    }
+}
+
+impl fmt::Display for CodecPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodecPosition::Offset(offset) => write!(f, "offset {}", offset),
+            CodecPosition::LineColumn(line, column) => write!(f, "line {} column {}", line, column),
+        }
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(codec) = &self.codec {
+            write!(f, "{} ", codec)?;
+        }
+        self.kind.fmt(f)?;
+        if let Some(position) = &self.position {
+            write!(f, " at {}", position)?;
+        }
+        if let Some(source) = &self.source {
+            write!(f, ": {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.source.as_ref().map(|source| source.as_ref() as &(dyn error::Error + 'static))
+    }
+}
+
+pub trait BodyCodec {
+    fn subtype(&self) -> &str;
+}
+
+pub trait BodyDecoder<T>: BodyCodec {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<T>;
+}
+
+pub trait BodyEncoder<T>: BodyCodec {
+    fn encode_bytes(&self, data: T) -> CodecResult<Bytes>;
+}
+
+pub trait BinaryCodec: BodyDecoder<Bytes> + BodyEncoder<Bytes> {}
+
+impl<C> BinaryCodec for C where C: BodyDecoder<Bytes> + BodyEncoder<Bytes> {}
+
+pub trait DecodeData<T>: Sized {
+    type Data;
+
+    fn decode_data<C>(self, codec: &C) -> CodecResult<T>
+        where C: BodyDecoder<Self::Data> + ?Sized;
+}
+
+pub trait DecodeBody<T>: DecodeData<T> + ReadHeader + MatchType + UnwrapType
+    where T: WriteHeader
+{
+    #[inline]
+    fn decode_body_with_type<C, V>(self, codec: &C, mimetype: V) -> CodecResult<T>
+        where C: BodyDecoder<Self::Data> + ?Sized,
+              V: AsTypePattern
+    {
+        if mimetype.as_type_pattern().map(|pattern| self.match_type(pattern)).unwrap_or(false) {
+            self.decode_data(codec)
+        } else {
+            Err(CodecError::invalid_type(codec.subtype()))
+        }
+    }
+
+    #[inline]
+    fn decode_body_auto_type<C>(self, codec: &C) -> CodecResult<T>
+        where C: BodyDecoder<Self::Data> + ?Sized
+    {
+        let mimetype = self.unwrap_type(codec.subtype())
+            .map_err(|error| CodecError::invalid_type(codec.subtype()).with_source(error))?
+            .ok_or_else(|| CodecError::invalid_type(codec.subtype()))?;
+        self.decode_data(codec).map(move |mut new_self| {
+            new_self.set_header("Content-Type", mimetype);
+            new_self
+        })
+    }
+}
+
+impl<T> DecodeData<T> for Bytes {
+    type Data = T;
+
+    fn decode_data<C>(self, codec: &C) -> CodecResult<T>
+        where C: BodyDecoder<T> + ?Sized
+    {
+        codec.decode_bytes(self)
+    }
+}
+
+impl<T> DecodeData<Request<T>> for Request<Bytes> {
+    type Data = T;
+
+    fn decode_data<C>(self, codec: &C) -> CodecResult<Request<T>>
+        where C: BodyDecoder<T> + ?Sized
+    {
+        let (parts, body) = self.into_parts();
+        let body = codec.decode_bytes(body)?;
+        Ok(Request::from_parts(parts, body))
+    }
+}
+
+impl<T> DecodeBody<Request<T>> for Request<Bytes> {}
+
+impl<T> DecodeData<Response<T>> for Response<Bytes> {
+    type Data = T;
+
+    fn decode_data<C>(self, codec: &C) -> CodecResult<Response<T>>
+        where C: BodyDecoder<T> + ?Sized
+    {
+        let (parts, body) = self.into_parts();
+        let body = codec.decode_bytes(body)?;
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+impl<T> DecodeBody<Response<T>> for Response<Bytes> {}
+
+pub trait EncodeData<T>: Sized {
+    type Data;
+
+    fn encode_data<C>(self, codec: &C) -> CodecResult<T>
+        where C: BodyEncoder<Self::Data> + ?Sized;
+}
+
+pub trait EncodeBody<T>: EncodeData<T> + ReadHeader + WrapType
+    where T: WriteHeader
+{
+    #[inline]
+    fn encode_body_with_type<C>(self, codec: &C, mimetype: &'static str) -> CodecResult<T>
+        where C: BodyEncoder<Self::Data> + ?Sized
+    {
+        self.encode_data(codec)
+            .map(|mut new_self| {
+                new_self.set_header("Content-Type", HeaderValue::from_static(mimetype));
+                new_self
+            })
+    }
+
+    #[inline]
+    fn encode_body_auto_type<C>(self, codec: &C) -> CodecResult<T>
+        where C: BodyEncoder<Self::Data> + ?Sized
+    {
+        let mimetype = self.wrap_type(codec.subtype())
+            .map_err(|error| CodecError::invalid_type(codec.subtype()).with_source(error))?
+            .ok_or_else(|| CodecError::invalid_type(codec.subtype()))?;
+        self.encode_data(codec)
+            .map(move |mut new_self| {
+                new_self.set_header("Content-Type", mimetype);
+                new_self
+            })
+    }
+}
+
+impl<T> EncodeData<Bytes> for T {
+    type Data = T;
+
+    fn encode_data<C>(self, codec: &C) -> CodecResult<Bytes>
+        where C: BodyEncoder<T> + ?Sized
+    {
+        codec.encode_bytes(self)
+    }
+}
+
+impl<T> EncodeData<Request<Bytes>> for Request<T> {
+    type Data = T;
+
+    fn encode_data<C>(self, codec: &C) -> CodecResult<Request<Bytes>>
+        where C: BodyEncoder<T> + ?Sized
+    {
+        let (parts, body) = self.into_parts();
+        let body = codec.encode_bytes(body)?;
+        Ok(Request::from_parts(parts, body))
+    }
+}
+
+impl<T> EncodeBody<Request<Bytes>> for Request<T> {}
+
+impl<T> EncodeData<Response<Bytes>> for Response<T> {
+    type Data = T;
+
+    fn encode_data<C>(self, codec: &C) -> CodecResult<Response<Bytes>>
+        where C: BodyEncoder<T> + ?Sized
+    {
+        let (parts, body) = self.into_parts();
+        let body = codec.encode_bytes(body)?;
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+impl<T> EncodeBody<Response<Bytes>> for Response<T> {}
+
+#[derive(Default)]
+pub struct CodecRegistry {
+    codecs: HashMap<String, Box<dyn BinaryCodec>>,
+    // the box is kept apart because it reports the peer key
+    cryptobox: Option<Cryptobox>,
+}
+
+impl CodecRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_codec<C>(mut self, codec: C) -> Self
+        where C: BinaryCodec + 'static
+    {
+        self.add_codec(codec);
+        self
+    }
+
+    pub fn add_codec<C>(&mut self, codec: C)
+        where C: BinaryCodec + 'static
+    {
+        self.codecs.insert(codec.subtype().into(), Box::new(codec));
+    }
+
+    pub fn get_codec(&self, subtype: &str) -> Option<&dyn BinaryCodec> {
+        self.codecs.get(subtype).map(|codec| codec.as_ref())
+    }
+
+    pub fn with_cryptobox(mut self, cryptobox: Cryptobox) -> Self {
+        self.set_cryptobox(cryptobox);
+        self
+    }
+
+    pub fn set_cryptobox(&mut self, cryptobox: Cryptobox) {
+        self.cryptobox = Some(cryptobox);
+    }
+
+    pub fn get_cryptobox(&self) -> Option<&Cryptobox> {
+        self.cryptobox.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request};
+    use super::*;
+
+    struct Reverse;
+
+    impl BodyCodec for Reverse {
+        fn subtype(&self) -> &str {
+            "reverse"
+        }
+    }
+
+    impl BodyDecoder<Bytes> for Reverse {
+        fn decode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+            Ok(data.iter().rev().cloned().collect::<Vec<_>>().into())
+        }
+    }
+
+    impl BodyEncoder<Bytes> for Reverse {
+        fn encode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+            self.decode_bytes(data)
+        }
+    }
+
+    #[test]
+    fn test_custom_codec() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "text/plain")
+            .body("hello".into())
+            .unwrap();
+        let e: Request<Bytes> = a.encode_body_auto_type(&Reverse).unwrap();
+
+        assert!(e.is_header("Content-Type", "text/plain+reverse"));
+        assert_eq!(e.body(), "olleh");
+
+        let d: Request<Bytes> = e.decode_body_auto_type(&Reverse).unwrap();
+
+        assert!(d.is_header("Content-Type", "text/plain"));
+        assert_eq!(d.into_body(), "hello");
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = CodecRegistry::new()
+            .with_codec(Reverse);
+        let codec = registry.get_codec("reverse").unwrap();
+
+        assert_eq!(codec.subtype(), "reverse");
+        assert_eq!(codec.decode_bytes("hello".into()).unwrap(), "olleh");
+        assert!(registry.get_codec("base64").is_none());
+    }
+
+    #[test]
+    fn test_error() {
+        let e = CodecError::invalid_data("json")
+            .with_position(CodecPosition::LineColumn(1, 2))
+            .with_source("expected value");
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidData);
+        assert_eq!(e.codec(), Some("json"));
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(e.to_string(), "json Invalid data at line 1 column 2: expected value");
+        assert!(error::Error::source(&e).is_some());
+
+        assert_eq!(CodecError::invalid_type("json").status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(CodecError::invalid_value("json").status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(CodecError::encode_failed("json").status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
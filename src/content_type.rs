@@ -1,11 +1,44 @@
 use std::borrow::Cow;
+use std::{error, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentTypeError {
+    InvalidType(usize),
+    InvalidSubtype(usize),
+    InvalidParam(usize),
+}
+
+impl ContentTypeError {
+    pub fn position(&self) -> usize {
+        use self::ContentTypeError::*;
+        match *self {
+            InvalidType(position) | InvalidSubtype(position) | InvalidParam(position) => position,
+        }
+    }
+}
+
+impl fmt::Display for ContentTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::ContentTypeError::*;
+        f.write_str(match self {
+            InvalidType(_) => "Invalid media type",
+            InvalidSubtype(_) => "Invalid media subtype",
+            InvalidParam(_) => "Invalid media type parameter",
+        })?;
+        write!(f, " at {}", self.position())
+    }
+}
+
+impl error::Error for ContentTypeError {}
 
 #[derive(Debug, Clone)]
 pub struct ContentType<'ct> {
     fulltype: Cow<'ct, str>,
     typeoffs: Vec<usize>,
+    params: Vec<(String, String)>,
 }
 
+// parameters does not affect the media type itself
 impl<'ct1, 'ct2> PartialEq<ContentType<'ct2>> for ContentType<'ct1> {
     fn eq(&self, other: &ContentType<'ct2>) -> bool {
         self.as_ref() == other.as_ref()
@@ -18,12 +51,201 @@ impl<'ct> AsRef<str> for ContentType<'ct> {
     }
 }
 
+impl<'ct> fmt::Display for ContentType<'ct> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_ref())?;
+        for (name, value) in &self.params {
+            if !value.is_empty() && value.bytes().all(is_token_char) {
+                write!(f, "; {}={}", name, value)?;
+            } else {
+                write!(f, "; {}=\"", name)?;
+                for chr in value.chars() {
+                    if chr == '"' || chr == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{}", chr)?;
+                }
+                f.write_str("\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_token_char(chr: u8) -> bool {
+    chr.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&chr)
+}
+
+fn skip_token<F>(src: &[u8], mut pos: usize, is_char: F) -> usize
+    where F: Fn(u8) -> bool
+{
+    while pos < src.len() && is_char(src[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+fn skip_space(src: &[u8], pos: usize) -> usize {
+    skip_token(src, pos, |chr| chr == b' ' || chr == b'\t')
+}
+
+// validates media type syntax and returns the end of type and subtypes
+fn validate(src: &str) -> Result<usize, ContentTypeError> {
+    use self::ContentTypeError::*;
+    let src = src.as_bytes();
+
+    let mut pos = skip_token(src, 0, |chr| chr != b'/' && is_token_char(chr));
+    if pos == 0 {
+        return Err(InvalidType(pos));
+    }
+
+    // the bare type without subtypes is allowed because
+    // it is what remains when the last subtype is unwrapped
+    if src.get(pos) == Some(&b'/') {
+        // the '+' is a valid token char, but we treat it as subtypes separator
+        loop {
+            let end = skip_token(src, pos + 1, |chr| chr != b'+' && is_token_char(chr));
+            if end == pos + 1 {
+                return Err(InvalidSubtype(end));
+            }
+            pos = end;
+            if src.get(pos) != Some(&b'+') {
+                break;
+            }
+        }
+    }
+    let type_end = pos;
+
+    loop {
+        pos = skip_space(src, pos);
+        if pos == src.len() {
+            return Ok(type_end);
+        }
+        if src[pos] != b';' {
+            return Err(InvalidParam(pos));
+        }
+        pos = skip_space(src, pos + 1);
+        if pos == src.len() || src[pos] == b';' {
+            continue;
+        }
+        let end = skip_token(src, pos, is_token_char);
+        if end == pos || src.get(end) != Some(&b'=') {
+            return Err(InvalidParam(end));
+        }
+        pos = end + 1;
+        if src.get(pos) == Some(&b'"') {
+            pos += 1;
+            loop {
+                match src.get(pos) {
+                    Some(b'"') => break,
+                    Some(b'\\') if pos + 1 < src.len() => pos += 2,
+                    Some(&chr) if chr == b'\t' || (chr >= 0x20 && chr != 0x7f && chr != b'\\') => pos += 1,
+                    _ => return Err(InvalidParam(pos)),
+                }
+            }
+            pos += 1;
+        } else {
+            let end = skip_token(src, pos, is_token_char);
+            if end == pos {
+                return Err(InvalidParam(pos));
+            }
+            pos = end;
+        }
+    }
+}
+
+fn parse_params(src: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut chars = src.chars().peekable();
+    loop {
+        // skip separators and whitespaces before name
+        while let Some(&chr) = chars.peek() {
+            if chr == ';' || chr.is_whitespace() {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut name = String::new();
+        while let Some(&chr) = chars.peek() {
+            if chr == '=' || chr == ';' {
+                break;
+            }
+            name.push(chr);
+            chars.next();
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            while let Some(&chr) = chars.peek() {
+                if chr.is_whitespace() {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                while let Some(chr) = chars.next() {
+                    match chr {
+                        '"' => break,
+                        '\\' => if let Some(chr) = chars.next() {
+                            value.push(chr);
+                        },
+                        chr => value.push(chr),
+                    }
+                }
+                // ignore garbage after closing quote
+                while let Some(&chr) = chars.peek() {
+                    if chr == ';' {
+                        break;
+                    }
+                    chars.next();
+                }
+            } else {
+                while let Some(&chr) = chars.peek() {
+                    if chr == ';' {
+                        break;
+                    }
+                    value.push(chr);
+                    chars.next();
+                }
+                let len = value.trim_end().len();
+                value.truncate(len);
+            }
+        }
+        let name = name.trim();
+        if !name.is_empty() {
+            params.push((name.to_ascii_lowercase(), value));
+        }
+    }
+    params
+}
+
 impl<'ct> ContentType<'ct> {
     pub fn new<S>(src: S) -> Self
     where
         S: Into<Cow<'ct, str>>,
     {
-        let fulltype = src.into();
+        let mut fulltype = src.into();
+        let mut params = Vec::new();
+        let params_start = fulltype.find(';');
+        if let Some(params_start) = params_start {
+            params = parse_params(&fulltype[params_start + 1..]);
+        }
+        let type_end = fulltype[..params_start.unwrap_or(fulltype.len())].trim_end().len();
+        if type_end < fulltype.len() {
+            fulltype = match fulltype {
+                Cow::Borrowed(src) => Cow::Borrowed(&src[..type_end]),
+                Cow::Owned(mut src) => {
+                    src.truncate(type_end);
+                    Cow::Owned(src)
+                }
+            };
+        }
         let mut typeoffs = Vec::new();
         {
             let src = fulltype.as_ref();
@@ -44,7 +266,20 @@ impl<'ct> ContentType<'ct> {
                 typeoffs.push(src.len());
             }
         }
-        ContentType { fulltype, typeoffs }
+        ContentType { fulltype, typeoffs, params }
+    }
+
+    // unlike new() this rejects malformed media types and normalizes case
+    pub fn parse<S>(src: S) -> Result<Self, ContentTypeError>
+    where
+        S: Into<Cow<'ct, str>>,
+    {
+        let mut src = src.into();
+        let type_end = validate(&src)?;
+        if src[..type_end].bytes().any(|chr| chr.is_ascii_uppercase()) {
+            src.to_mut()[..type_end].make_ascii_lowercase();
+        }
+        Ok(ContentType::new(src))
     }
 
     pub fn get_type(&self) -> &str {
@@ -66,7 +301,8 @@ impl<'ct> ContentType<'ct> {
     }
 
     pub fn last_subtype(&self) -> Option<&str> {
-        self.get_subtype(self.num_subtypes() - 1)
+        self.num_subtypes().checked_sub(1)
+            .and_then(|index| self.get_subtype(index))
     }
 
     pub fn pop_subtype(&mut self) {
@@ -77,15 +313,67 @@ impl<'ct> ContentType<'ct> {
 
     pub fn push_subtype(&mut self, subtype: &str) {
         let has_subtypes = self.num_subtypes() > 0;
+        let type_end = self.typeoffs[self.typeoffs.len() - 1];
         let fulltype = self.fulltype.to_mut();
+        // drop the tail of previously popped subtypes
+        fulltype.truncate(type_end);
         fulltype.push(if has_subtypes { '+' } else { '/' });
         fulltype.push_str(subtype);
         self.typeoffs.push(fulltype.len());
     }
 
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_param(&mut self, name: &str, value: &str) {
+        if let Some(param) = self.params.iter_mut()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name)) {
+            param.1 = value.into();
+            return;
+        }
+        self.params.push((name.to_ascii_lowercase(), value.into()));
+    }
+
+    pub fn num_params(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn iter_params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    // the pattern may contain wildcards like */*, type/* or type/*+suffix,
+    // parameters are not taken into account
+    pub fn matches(&self, pattern: &ContentType) -> bool {
+        if pattern.get_type() == "*" {
+            return pattern.get_subtype(0) == Some("*") && pattern.num_subtypes() == 1;
+        }
+        if !self.get_type().eq_ignore_ascii_case(pattern.get_type()) {
+            return false;
+        }
+        let num_subtypes = self.num_subtypes();
+        let num_pattern_subtypes = pattern.num_subtypes();
+        let subtype_eq = |index, pattern_index| match (self.get_subtype(index), pattern.get_subtype(pattern_index)) {
+            (Some(subtype), Some(pattern)) => subtype.eq_ignore_ascii_case(pattern),
+            _ => false,
+        };
+        if pattern.get_subtype(0) == Some("*") {
+            // the wildcard covers at least one leading subtype
+            num_pattern_subtypes <= num_subtypes &&
+                (1..num_pattern_subtypes)
+                .all(|index| subtype_eq(num_subtypes - index, num_pattern_subtypes - index))
+        } else {
+            num_pattern_subtypes == num_subtypes &&
+                (0..num_subtypes).all(|index| subtype_eq(index, index))
+        }
+    }
+
     pub fn iter_subtypes(&'ct self) -> ContentTypeSubtypesIterator<'ct> {
         ContentTypeSubtypesIterator {
-            content_type: &self,
+            content_type: self,
             subtype_index: 0,
         }
     }
@@ -192,4 +480,119 @@ mod tests {
         ct.push_subtype("base64");
         assert_eq!(ct.as_ref(), "application/json+sbox+base64");
     }
+
+    #[test]
+    fn test_push_subtype_after_pop() {
+        let mut ct = ContentType::new("application/json+sbox");
+        ct.pop_subtype();
+        ct.push_subtype("base64");
+        assert_eq!(ct.as_ref(), "application/json+base64");
+        assert_eq!(ct.last_subtype(), Some("base64"));
+    }
+
+    #[test]
+    fn test_get_param() {
+        let ct = ContentType::new("application/json; charset=utf-8");
+        assert_eq!(ct.as_ref(), "application/json");
+        assert_eq!(ct.num_subtypes(), 1);
+        assert_eq!(ct.last_subtype(), Some("json"));
+        assert_eq!(ct.get_param("charset"), Some("utf-8"));
+        assert_eq!(ct.get_param("CharSet"), Some("utf-8"));
+        assert_eq!(ct.get_param("boundary"), None);
+    }
+
+    #[test]
+    fn test_get_param_quoted() {
+        let ct = ContentType::new("multipart/form-data;Boundary=\"a; \\\"b\\\"\" ; q=0.5");
+        assert_eq!(ct.as_ref(), "multipart/form-data");
+        assert_eq!(ct.num_params(), 2);
+        assert_eq!(ct.get_param("boundary"), Some("a; \"b\""));
+        assert_eq!(ct.get_param("q"), Some("0.5"));
+    }
+
+    #[test]
+    fn test_set_param() {
+        let mut ct = ContentType::new("text/plain; Charset=latin1");
+        ct.set_param("charset", "utf-8");
+        ct.set_param("format", "flowed text");
+        assert_eq!(ct.to_string(), "text/plain; charset=utf-8; format=\"flowed text\"");
+    }
+
+    #[test]
+    fn test_params_push_pop_subtype() {
+        let mut ct = ContentType::new("application/json+base64; charset=utf-8");
+        ct.pop_subtype();
+        assert_eq!(ct.to_string(), "application/json; charset=utf-8");
+        ct.push_subtype("sbox");
+        assert_eq!(ct.to_string(), "application/json+sbox; charset=utf-8");
+    }
+
+    #[test]
+    fn test_matches() {
+        let ct = ContentType::new("application/problem+json");
+        assert!(ct.matches(&ContentType::new("*/*")));
+        assert!(ct.matches(&ContentType::new("application/*")));
+        assert!(ct.matches(&ContentType::new("application/*+json")));
+        assert!(ct.matches(&ContentType::new("Application/Problem+JSON; charset=utf-8")));
+        assert!(!ct.matches(&ContentType::new("text/*")));
+        assert!(!ct.matches(&ContentType::new("application/*+cbor")));
+        assert!(!ct.matches(&ContentType::new("application/json")));
+        assert!(!ct.matches(&ContentType::new("*/json")));
+        assert!(!ContentType::new("application/json").matches(&ContentType::new("application/*+json")));
+        assert!(ContentType::new("application/vnd.literium.v1+json+base64")
+                .matches(&ContentType::new("application/*+json+base64")));
+    }
+
+    #[test]
+    fn test_eq_ignores_params() {
+        assert_eq!(
+            ContentType::new("application/json; charset=utf-8"),
+            ContentType::new("application/json")
+        );
+        assert!(ContentType::new("application/json") != ContentType::new("application/cbor"));
+    }
+
+    #[test]
+    fn test_last_subtype_no_subtypes() {
+        assert_eq!(ContentType::new("application-json").last_subtype(), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let ct = ContentType::parse("Application/Vnd.Literium.V1+JSON ;Charset=UTF-8;; q=1 ").unwrap();
+        assert_eq!(ct.as_ref(), "application/vnd.literium.v1+json");
+        assert_eq!(ct.last_subtype(), Some("json"));
+        assert_eq!(ct.get_param("charset"), Some("UTF-8"));
+        assert_eq!(ct.get_param("q"), Some("1"));
+    }
+
+    #[test]
+    fn test_parse_no_subtypes() {
+        let ct = ContentType::parse("Application; charset=utf-8").unwrap();
+        assert_eq!(ct.as_ref(), "application");
+        assert_eq!(ct.num_subtypes(), 0);
+        assert_eq!(ct.last_subtype(), None);
+    }
+
+    #[test]
+    fn test_parse_quoted() {
+        let ct = ContentType::parse("multipart/form-data; boundary=\"a \\\" b\"").unwrap();
+        assert_eq!(ct.get_param("boundary"), Some("a \" b"));
+    }
+
+    #[test]
+    fn test_parse_err() {
+        use self::ContentTypeError::*;
+        assert_eq!(ContentType::parse("").unwrap_err(), InvalidType(0));
+        assert_eq!(ContentType::parse("/json").unwrap_err(), InvalidType(0));
+        assert_eq!(ContentType::parse("application json").unwrap_err(), InvalidParam(12));
+        assert_eq!(ContentType::parse("application/").unwrap_err(), InvalidSubtype(12));
+        assert_eq!(ContentType::parse("application/json+").unwrap_err(), InvalidSubtype(17));
+        assert_eq!(ContentType::parse("application/json++base64").unwrap_err(), InvalidSubtype(17));
+        assert_eq!(ContentType::parse("application/json base64").unwrap_err(), InvalidParam(17));
+        assert_eq!(ContentType::parse("text/plain; charset").unwrap_err(), InvalidParam(19));
+        assert_eq!(ContentType::parse("text/plain; charset=").unwrap_err(), InvalidParam(20));
+        assert_eq!(ContentType::parse("text/plain; charset = utf-8").unwrap_err(), InvalidParam(19));
+        assert_eq!(ContentType::parse("text/plain; charset=\"utf-8").unwrap_err(), InvalidParam(26));
+    }
 }
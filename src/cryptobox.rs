@@ -0,0 +1,289 @@
+use http::{Request, Response};
+use bytes::{Bytes};
+use sodiumoxide::crypto::box_::{self, Nonce, NONCEBYTES, MACBYTES};
+use super::{WriteHeader, AsTypePattern, CodecError, CodecResult, BodyCodec, BodyDecoder, BodyEncoder,
+            DecodeData, DecodeBody, EncodeData, EncodeBody, PublicKey, SecretKey};
+
+// The peer_keys is a set of public keys of senders which are allowed
+// to open the box, the first one is the key of receiver to seal the box.
+#[derive(Debug, Clone)]
+pub struct Cryptobox {
+    secret_key: SecretKey,
+    peer_keys: Vec<PublicKey>,
+}
+
+impl Cryptobox {
+    pub fn new(secret_key: SecretKey) -> Self {
+        Self { secret_key, peer_keys: Vec::new() }
+    }
+
+    pub fn with_peer_key(mut self, peer_key: PublicKey) -> Self {
+        self.peer_keys.push(peer_key);
+        self
+    }
+
+    pub fn with_peer_keys(mut self, peer_keys: &[PublicKey]) -> Self {
+        self.peer_keys.extend_from_slice(peer_keys);
+        self
+    }
+}
+
+impl BodyCodec for Cryptobox {
+    fn subtype(&self) -> &str {
+        "box"
+    }
+}
+
+// the key which authenticates the message is returned with data
+impl BodyDecoder<(Bytes, PublicKey)> for Cryptobox {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<(Bytes, PublicKey)> {
+        let nonce = data.get(..NONCEBYTES).and_then(Nonce::from_slice)
+            .ok_or_else(|| CodecError::invalid_data(self.subtype())
+                        .with_source("Missing nonce"))?;
+        let data = &data[NONCEBYTES..];
+        for peer_key in &self.peer_keys {
+            if let Ok(body) = box_::open(data, &nonce, peer_key, &self.secret_key) {
+                return Ok((body.into(), *peer_key));
+            }
+        }
+        Err(CodecError::invalid_data(self.subtype())
+            .with_source("Unable to open box with any of peer keys"))
+    }
+}
+
+impl BodyEncoder<Bytes> for Cryptobox {
+    fn encode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        let peer_key = self.peer_keys.first()
+            .ok_or_else(|| CodecError::misconfigured(self.subtype())
+                        .with_source("Peer key required to seal"))?;
+        let nonce = box_::gen_nonce();
+        let mut body = Vec::with_capacity(NONCEBYTES + data.len() + MACBYTES);
+        body.extend_from_slice(&nonce.0);
+        body.extend_from_slice(&box_::seal(&data, &nonce, peer_key, &self.secret_key));
+        Ok(body.into())
+    }
+}
+
+// Splits the peer key out of decoded data.
+pub trait UnwrapPeerKey {
+    type Output;
+
+    fn unwrap_peer_key(self) -> (Self::Output, PublicKey);
+}
+
+impl<T> UnwrapPeerKey for (T, PublicKey) {
+    type Output = T;
+
+    fn unwrap_peer_key(self) -> (T, PublicKey) {
+        self
+    }
+}
+
+impl<T> UnwrapPeerKey for Request<(T, PublicKey)> {
+    type Output = Request<T>;
+
+    fn unwrap_peer_key(self) -> (Request<T>, PublicKey) {
+        let (parts, (body, peer_key)) = self.into_parts();
+        (Request::from_parts(parts, body), peer_key)
+    }
+}
+
+impl<T> UnwrapPeerKey for Response<(T, PublicKey)> {
+    type Output = Response<T>;
+
+    fn unwrap_peer_key(self) -> (Response<T>, PublicKey) {
+        let (parts, (body, peer_key)) = self.into_parts();
+        (Response::from_parts(parts, body), peer_key)
+    }
+}
+
+// the key is kept in extensions when the box is opened by chain
+#[derive(Debug, Clone, Copy)]
+struct PeerKey(PublicKey);
+
+pub trait ReadPeerKey {
+    fn peer_key(&self) -> Option<PublicKey>;
+}
+
+impl<T> ReadPeerKey for Request<T> {
+    fn peer_key(&self) -> Option<PublicKey> {
+        self.extensions().get::<PeerKey>().map(|peer_key| peer_key.0)
+    }
+}
+
+impl<T> ReadPeerKey for Response<T> {
+    fn peer_key(&self) -> Option<PublicKey> {
+        self.extensions().get::<PeerKey>().map(|peer_key| peer_key.0)
+    }
+}
+
+// Opens the box in chain keeping the peer key in extensions.
+pub trait OpenBoxChain: Sized {
+    fn open_box_chain(self, cryptobox: &Cryptobox) -> CodecResult<Self>;
+}
+
+impl OpenBoxChain for Request<Bytes> {
+    fn open_box_chain(self, cryptobox: &Cryptobox) -> CodecResult<Self> {
+        let (mut this, peer_key) = DecodeBody::<Request<(Bytes, PublicKey)>>::decode_body_auto_type(self, cryptobox)?
+            .unwrap_peer_key();
+        this.extensions_mut().insert(PeerKey(peer_key));
+        Ok(this)
+    }
+}
+
+impl OpenBoxChain for Response<Bytes> {
+    fn open_box_chain(self, cryptobox: &Cryptobox) -> CodecResult<Self> {
+        let (mut this, peer_key) = DecodeBody::<Response<(Bytes, PublicKey)>>::decode_body_auto_type(self, cryptobox)?
+            .unwrap_peer_key();
+        this.extensions_mut().insert(PeerKey(peer_key));
+        Ok(this)
+    }
+}
+
+pub trait DecryptBox<T>: Sized
+    where T: UnwrapPeerKey
+{
+    fn decrypt_box(self, peer_keys: &[PublicKey], secret_key: &SecretKey) -> CodecResult<(T::Output, PublicKey)>;
+}
+
+impl<T, B> DecryptBox<T> for B
+    where B: DecodeData<T, Data = (Bytes, PublicKey)>,
+          T: UnwrapPeerKey
+{
+    #[inline]
+    fn decrypt_box(self, peer_keys: &[PublicKey], secret_key: &SecretKey) -> CodecResult<(T::Output, PublicKey)> {
+        self.decode_data(&Cryptobox::new(secret_key.clone()).with_peer_keys(peer_keys))
+            .map(UnwrapPeerKey::unwrap_peer_key)
+    }
+}
+
+pub trait DecryptBoxBody<T>: DecryptBox<T>
+    where T: UnwrapPeerKey + WriteHeader
+{
+    fn decrypt_box_with_type<V>(self, peer_keys: &[PublicKey], secret_key: &SecretKey, mimetype: V) -> CodecResult<(T::Output, PublicKey)>
+        where V: AsTypePattern;
+
+    fn decrypt_box_auto_type(self, peer_keys: &[PublicKey], secret_key: &SecretKey) -> CodecResult<(T::Output, PublicKey)>;
+}
+
+impl<T, B> DecryptBoxBody<T> for B
+    where B: DecodeBody<T, Data = (Bytes, PublicKey)>,
+          T: UnwrapPeerKey + WriteHeader
+{
+    #[inline]
+    fn decrypt_box_with_type<V>(self, peer_keys: &[PublicKey], secret_key: &SecretKey, mimetype: V) -> CodecResult<(T::Output, PublicKey)>
+        where V: AsTypePattern
+    {
+        self.decode_body_with_type(&Cryptobox::new(secret_key.clone()).with_peer_keys(peer_keys), mimetype)
+            .map(UnwrapPeerKey::unwrap_peer_key)
+    }
+
+    #[inline]
+    fn decrypt_box_auto_type(self, peer_keys: &[PublicKey], secret_key: &SecretKey) -> CodecResult<(T::Output, PublicKey)> {
+        self.decode_body_auto_type(&Cryptobox::new(secret_key.clone()).with_peer_keys(peer_keys))
+            .map(UnwrapPeerKey::unwrap_peer_key)
+    }
+}
+
+pub trait EncryptBox: Sized {
+    fn encrypt_box(self, peer_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self>;
+}
+
+impl<B> EncryptBox for B
+    where B: EncodeData<B, Data = Bytes>
+{
+    #[inline]
+    fn encrypt_box(self, peer_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self> {
+        self.encode_data(&Cryptobox::new(secret_key.clone()).with_peer_key(*peer_key))
+    }
+}
+
+pub trait EncryptBoxBody: EncryptBox + WriteHeader
+{
+    fn encrypt_box_with_type(self, peer_key: &PublicKey, secret_key: &SecretKey, mimetype: &'static str) -> CodecResult<Self>;
+
+    fn encrypt_box_auto_type(self, peer_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self>;
+}
+
+impl<B> EncryptBoxBody for B
+    where B: EncodeBody<B, Data = Bytes> + WriteHeader
+{
+    #[inline]
+    fn encrypt_box_with_type(self, peer_key: &PublicKey, secret_key: &SecretKey, mimetype: &'static str) -> CodecResult<Self> {
+        self.encode_body_with_type(&Cryptobox::new(secret_key.clone()).with_peer_key(*peer_key), mimetype)
+    }
+
+    #[inline]
+    fn encrypt_box_auto_type(self, peer_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self> {
+        self.encode_body_auto_type(&Cryptobox::new(secret_key.clone()).with_peer_key(*peer_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, StatusCode};
+    use super::super::{gen_keypair, ReadHeader, CodecErrorKind};
+    use super::*;
+
+    #[test]
+    fn test_box() {
+        let (client_pk, client_sk) = gen_keypair();
+        let (other_pk, _) = gen_keypair();
+        let (server_pk, server_sk) = gen_keypair();
+
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+plain")
+            .body("hello world".into())
+            .unwrap();
+
+        let e = a.encrypt_box_auto_type(&server_pk, &client_sk).unwrap();
+
+        assert!(e.is_header("Content-Type", "application/vnd.literium.v1+plain+box"));
+
+        let (d, pk) = e.decrypt_box_auto_type(&[other_pk, client_pk], &server_sk).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1+plain"));
+        assert_eq!(pk, client_pk);
+
+        assert_eq!(d.into_body(), "hello world");
+    }
+
+    #[test]
+    fn test_box_err_peer() {
+        let (_, client_sk) = gen_keypair();
+        let (other_pk, _) = gen_keypair();
+        let (server_pk, server_sk) = gen_keypair();
+
+        let a: Request<Bytes> = Request::builder()
+            .body("hello world".into())
+            .unwrap();
+
+        let e = a.encrypt_box(&server_pk, &client_sk).unwrap();
+        let d = e.decrypt_box(&[other_pk], &server_sk).map(|(_, pk): (Request<Bytes>, _)| pk);
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_box_bytes() {
+        let (client_pk, client_sk) = gen_keypair();
+        let (server_pk, server_sk) = gen_keypair();
+
+        let e = Bytes::from("hello").encrypt_box(&server_pk, &client_sk).unwrap();
+
+        assert_eq!(e.len(), NONCEBYTES + MACBYTES + 5);
+
+        let (d, pk): (Bytes, _) = e.decrypt_box(&[client_pk], &server_sk).unwrap();
+
+        assert_eq!(d, "hello");
+        assert_eq!(pk, client_pk);
+
+        let e = Bytes::from("hello").encode_data(&Cryptobox::new(client_sk))
+            .map(|_: Bytes| ());
+
+        let e = e.unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::Misconfigured);
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,513 @@
+use std::{cmp, fs, io};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{UNIX_EPOCH};
+use futures::{future, try_ready, Future, Stream, Poll, Async};
+use http::{Method, Request, Response, StatusCode};
+use http::header::{HeaderName, HeaderValue, ACCEPT_RANGES, ALLOW, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+                   ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, LOCATION, RANGE};
+use hyper::{Body};
+use time::{self, Timespec};
+use tokio_threadpool;
+use super::{ReadHeader, RoutedRequest, Segments, SegmentPolicy, HandlerError, HandlerFuture};
+use super::router::{empty_response};
+
+// the size of chunks which the file is read by
+const CHUNK_SIZE: usize = 65536;
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "application/javascript; charset=utf-8",
+        "json" | "map" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn http_date(secs: i64) -> String {
+    time::at_utc(Timespec::new(secs, 0)).rfc822().to_string()
+}
+
+fn parse_http_date(date: &str) -> Option<i64> {
+    time::strptime(date, "%a, %d %b %Y %T GMT").ok()
+        .map(|tm| tm.to_timespec().sec)
+}
+
+// the multiple and malformed ranges is ignored,
+// the unsatisfiable range gives `Some(None)`
+fn parse_range(range: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let range = range.trim();
+    if !range.starts_with("bytes=") || range.contains(',') {
+        return None;
+    }
+    let mut bounds = range["bytes=".len()..].splitn(2, '-');
+    let (start, end) = (bounds.next()?.trim(), bounds.next()?.trim());
+    if len == 0 {
+        return Some(None);
+    }
+    let (start, end) = match (start, end) {
+        ("", suffix) => match suffix.parse::<u64>().ok()? {
+            0 => return Some(None),
+            suffix => (len.saturating_sub(suffix), len - 1),
+        },
+        (start, "") => (start.parse().ok()?, len - 1),
+        (start, end) => {
+            let (start, end) = (start.parse().ok()?, end.parse::<u64>().ok()?);
+            if end < start {
+                return None;
+            }
+            (start, end.min(len - 1))
+        },
+    };
+    Some(if start < len { Some((start, end)) } else { None })
+}
+
+fn set_header(response: &mut Response<Body>, name: HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        response.headers_mut().insert(name, value);
+    }
+}
+
+fn io_status(error: &io::Error) -> StatusCode {
+    match error.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+// the blocking io is run on the pool when polled from its worker,
+// otherwise in place like on current thread executor
+fn poll_blocking<F, T>(f: F) -> Poll<T, io::Error>
+    where F: FnOnce() -> io::Result<T>
+{
+    let mut f = Some(f);
+    match tokio_threadpool::blocking(|| f.take().map(|f| f())) {
+        Ok(Async::Ready(Some(result))) => result.map(Async::Ready),
+        Ok(Async::NotReady) => Ok(Async::NotReady),
+        _ => match f.take() {
+            Some(f) => f().map(Async::Ready),
+            None => Err(io::ErrorKind::Other.into()),
+        },
+    }
+}
+
+// The stream of the range of file.
+struct FileChunks {
+    file: fs::File,
+    remaining: u64,
+}
+
+impl FileChunks {
+    fn open(path: &Path, start: u64, len: u64) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::Start(start))?;
+        Ok(FileChunks { file, remaining: len })
+    }
+}
+
+impl Stream for FileChunks {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, io::Error> {
+        if self.remaining == 0 {
+            return Ok(Async::Ready(None));
+        }
+        let len = cmp::min(self.remaining, CHUNK_SIZE as u64) as usize;
+        let file = &mut self.file;
+        let data = try_ready!(poll_blocking(|| {
+            let mut data = vec![0; len];
+            let read = file.read(&mut data)?;
+            data.truncate(read);
+            Ok(data)
+        }));
+        // the file is truncated after the length was sent
+        if data.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= data.len() as u64;
+        Ok(Async::Ready(Some(data)))
+    }
+}
+
+// The handler which serves the files from directory
+// using the rest of routed path.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    fallback: bool,
+}
+
+impl StaticFiles {
+    pub fn new<P>(root: P) -> Self
+        where P: Into<PathBuf>
+    {
+        StaticFiles { root: root.into(), fallback: false }
+    }
+
+    // serves the root index.html when file is not found
+    // to support client-side routes
+    pub fn with_fallback(self, fallback: bool) -> Self {
+        Self { fallback, ..self }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // the file is looked up on the blocking pool and streamed by chunks
+    pub fn handle(&self, request: RoutedRequest<Body>) -> HandlerFuture {
+        let files = self.clone();
+        Box::new(future::poll_fn(move || poll_blocking(|| Ok(files.respond(&request))))
+                 .map_err(HandlerError::from))
+    }
+
+    // the dot files and segments with drive separator is hidden,
+    // the decoded path separators is rejected by segments
+    fn resolve(&self, path: &str) -> Result<PathBuf, StatusCode> {
+        let mut file = self.root.clone();
+        for segment in Segments::new(path, SegmentPolicy::Lenient) {
+            let segment = segment.map_err(|error| error.status())?;
+            if segment.starts_with('.') || segment.contains(':') {
+                return Err(StatusCode::NOT_FOUND);
+            }
+            file.push(segment.as_ref());
+        }
+        Ok(file)
+    }
+
+    fn lookup(&self, mut file: PathBuf) -> io::Result<(PathBuf, fs::Metadata)> {
+        let mut meta = fs::metadata(&file)?;
+        if meta.is_dir() {
+            file.push("index.html");
+            meta = fs::metadata(&file)?;
+        }
+        // the symlinks is not followed outside of root
+        if !meta.is_file() || !fs::canonicalize(&file)?.starts_with(fs::canonicalize(&self.root)?) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok((file, meta))
+    }
+
+    fn respond(&self, request: &RoutedRequest<Body>) -> Response<Body> {
+        let method = request.request().method();
+        if method != Method::GET && method != Method::HEAD {
+            let mut response = empty_response(StatusCode::METHOD_NOT_ALLOWED);
+            set_header(&mut response, ALLOW, "GET, HEAD");
+            return response;
+        }
+
+        let file = match self.resolve(request.path()) {
+            Ok(file) => file,
+            Err(status) => return empty_response(status),
+        };
+        // the relative links of index.html is resolved against the directory
+        if !request.path().ends_with('/') && fs::metadata(&file).map(|meta| meta.is_dir()).unwrap_or(false) {
+            let uri = request.request().uri();
+            let mut location = format!("{}/", uri.path());
+            if let Some(query) = uri.query() {
+                location.push('?');
+                location.push_str(query);
+            }
+            let mut response = empty_response(StatusCode::MOVED_PERMANENTLY);
+            set_header(&mut response, LOCATION, &location);
+            return response;
+        }
+        let found = match self.lookup(file) {
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound && self.fallback =>
+                self.lookup(self.root.join("index.html")),
+            found => found,
+        };
+        match found {
+            Ok((file, meta)) => self.respond_file(request.request(), &file, &meta),
+            Err(error) => empty_response(io_status(&error)),
+        }
+    }
+
+    fn respond_file(&self, request: &Request<Body>, file: &Path, meta: &fs::Metadata) -> Response<Body> {
+        let len = meta.len();
+        let modified = meta.modified().ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let etag = format!("\"{:x}-{:x}-{:x}\"", len, modified.as_secs(), modified.subsec_nanos());
+        let last_modified = http_date(modified.as_secs() as i64);
+
+        let not_modified = match request.get_header_str(IF_NONE_MATCH) {
+            Some(tags) => tags.split(',')
+                .map(|tag| tag.trim())
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag),
+            None => request.get_header_str(IF_MODIFIED_SINCE)
+                .and_then(parse_http_date)
+                .map(|since| modified.as_secs() as i64 <= since)
+                .unwrap_or(false),
+        };
+
+        let mut response = empty_response(if not_modified { StatusCode::NOT_MODIFIED } else { StatusCode::OK });
+        set_header(&mut response, ETAG, &etag);
+        set_header(&mut response, LAST_MODIFIED, &last_modified);
+        if not_modified {
+            return response;
+        }
+        set_header(&mut response, CONTENT_TYPE, mime_type(file));
+        set_header(&mut response, ACCEPT_RANGES, "bytes");
+
+        // the range is applied only when the validator of if-range is actual
+        let range = request.get_header_str(RANGE)
+            .filter(|_| request.get_header_str(IF_RANGE)
+                    .map(|validator| validator == etag || validator == last_modified)
+                    .unwrap_or(true))
+            .and_then(|range| parse_range(range, len));
+        let (start, end) = match range {
+            Some(Some((start, end))) => {
+                *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                set_header(&mut response, CONTENT_RANGE, &format!("bytes {}-{}/{}", start, end, len));
+                (start, end + 1)
+            },
+            Some(None) => {
+                let mut response = empty_response(StatusCode::RANGE_NOT_SATISFIABLE);
+                set_header(&mut response, CONTENT_RANGE, &format!("bytes */{}", len));
+                return response;
+            },
+            None => (0, len),
+        };
+        set_header(&mut response, CONTENT_LENGTH, &(end - start).to_string());
+
+        if request.method() == Method::GET {
+            match FileChunks::open(file, start, end - start) {
+                Ok(chunks) => *response.body_mut() = Body::wrap_stream(chunks),
+                Err(error) => return empty_response(io_status(&error)),
+            }
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+    use tokio_threadpool::{ThreadPool};
+    use super::*;
+    use super::super::{Router};
+
+    // the directory is removed when the test ends
+    struct TempRoot(PathBuf);
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn big_data() -> Vec<u8> {
+        (0..CHUNK_SIZE * 3 + 100).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn files(name: &str) -> (TempRoot, StaticFiles) {
+        let root = env::temp_dir().join(format!("literium-files-{}-{}", process::id(), name));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("index.html"), "<html></html>").unwrap();
+        fs::write(root.join("app.js"), "console.log(1);").unwrap();
+        fs::write(root.join(".secret"), "secret").unwrap();
+        fs::write(root.join("big.bin"), big_data()).unwrap();
+        fs::write(root.join("docs").join("index.html"), "docs").unwrap();
+        (TempRoot(root.clone()), StaticFiles::new(root))
+    }
+
+    // the response is handled and the body is read on the pool
+    fn handle(router: &Router, request: http::request::Builder) -> Response<Body> {
+        let mut request = request;
+        let response = router.handle(request.body(Body::empty()).unwrap().into())
+            .and_then(|response| {
+                let (parts, body) = response.into_parts();
+                body.concat2()
+                    .map(move |body| Response::from_parts(parts, Body::from(body)))
+                    .map_err(HandlerError::from)
+            });
+        ThreadPool::new().spawn_handle(response).wait().unwrap()
+    }
+
+    fn get(router: &Router, uri: &str) -> Response<Body> {
+        let mut request = Request::builder();
+        request.uri(uri);
+        handle(router, request)
+    }
+
+    fn body(response: Response<Body>) -> String {
+        let body = response.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn router(files: StaticFiles) -> Router {
+        Router::new().mount_handler("/static", move |request| files.handle(request))
+    }
+
+    #[test]
+    fn test_files_serve() {
+        let (_root, files) = files("serve");
+        let r = router(files);
+        let a = get(&r, "/static/app.js");
+
+        assert_eq!(a.status(), StatusCode::OK);
+        assert_eq!(a.headers()[CONTENT_TYPE], "application/javascript; charset=utf-8");
+        assert_eq!(a.headers()[CONTENT_LENGTH], "15");
+        assert!(a.headers().contains_key(ETAG));
+        assert!(a.headers().contains_key(LAST_MODIFIED));
+        assert_eq!(body(a), "console.log(1);");
+
+        assert_eq!(body(get(&r, "/static/")), "<html></html>");
+        assert_eq!(body(get(&r, "/static/docs/")), "docs");
+
+        let a = get(&r, "/static/docs?page=2");
+
+        assert_eq!(a.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(a.headers()[LOCATION], "/static/docs/?page=2");
+        assert_eq!(get(&r, "/static").headers()[LOCATION], "/static/");
+
+        let mut request = Request::builder();
+        request.method(Method::HEAD).uri("/static/app.js");
+        let a = handle(&r, request);
+
+        assert_eq!(a.headers()[CONTENT_LENGTH], "15");
+        assert_eq!(body(a), "");
+
+        let mut request = Request::builder();
+        request.method(Method::POST).uri("/static/app.js");
+        let a = handle(&r, request);
+
+        assert_eq!(a.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(a.headers()[ALLOW], "GET, HEAD");
+    }
+
+    #[test]
+    fn test_files_chunks() {
+        let (_root, files) = files("chunks");
+        let r = router(files);
+        let data = big_data();
+        let a = get(&r, "/static/big.bin");
+
+        assert_eq!(a.headers()[CONTENT_LENGTH], data.len().to_string().as_str());
+        assert_eq!(a.into_body().concat2().wait().unwrap().to_vec(), data);
+
+        let mut request = Request::builder();
+        request.uri("/static/big.bin").header(RANGE, format!("bytes={}-{}", CHUNK_SIZE - 10, CHUNK_SIZE * 2 + 9));
+        let a = handle(&r, request);
+
+        assert_eq!(a.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(a.into_body().concat2().wait().unwrap().to_vec(), &data[CHUNK_SIZE - 10..CHUNK_SIZE * 2 + 10]);
+    }
+
+    #[test]
+    fn test_files_traversal() {
+        let (_root, files) = files("traversal");
+        let r = router(files);
+
+        assert_eq!(get(&r, "/static/../Cargo.toml").status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&r, "/static/%2E%2E/Cargo.toml").status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&r, "/static/..%2FCargo.toml").status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&r, "/static/.secret").status(), StatusCode::NOT_FOUND);
+        assert_eq!(get(&r, "/static/missing.js").status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_files_fallback() {
+        let (_root, files) = files("fallback");
+        let r = router(files.with_fallback(true));
+        let a = get(&r, "/static/users/1");
+
+        assert_eq!(a.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert_eq!(body(a), "<html></html>");
+        assert_eq!(get(&r, "/static/.secret").status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_files_conditional() {
+        let (_root, files) = files("conditional");
+        let r = router(files);
+        let a = get(&r, "/static/app.js");
+        let etag = a.headers()[ETAG].to_str().unwrap().to_string();
+        let last_modified = a.headers()[LAST_MODIFIED].to_str().unwrap().to_string();
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(IF_NONE_MATCH, format!("\"other\", W/{}", etag));
+        let a = handle(&r, request);
+
+        assert_eq!(a.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(body(a), "");
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(IF_MODIFIED_SINCE, last_modified);
+        assert_eq!(handle(&r, request).status(), StatusCode::NOT_MODIFIED);
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(IF_MODIFIED_SINCE, "Sat, 01 Jan 2000 00:00:00 GMT");
+        assert_eq!(handle(&r, request).status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_files_range() {
+        let (_root, files) = files("range");
+        let r = router(files);
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(RANGE, "bytes=8-10");
+        let a = handle(&r, request);
+
+        assert_eq!(a.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(a.headers()[CONTENT_RANGE], "bytes 8-10/15");
+        assert_eq!(body(a), "log");
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(RANGE, "bytes=-4");
+        assert_eq!(body(handle(&r, request)), "(1);");
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(RANGE, "bytes=20-");
+        let a = handle(&r, request);
+
+        assert_eq!(a.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(a.headers()[CONTENT_RANGE], "bytes */15");
+
+        let mut request = Request::builder();
+        request.uri("/static/app.js").header(RANGE, "bytes=0-1").header(IF_RANGE, "\"stale\"");
+        let a = handle(&r, request);
+
+        assert_eq!(a.status(), StatusCode::OK);
+        assert_eq!(body(a), "console.log(1);");
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("bytes=0-", 10), Some(Some((0, 9))));
+        assert_eq!(parse_range("bytes=5-100", 10), Some(Some((5, 9))));
+        assert_eq!(parse_range("bytes=-20", 10), Some(Some((0, 9))));
+        assert_eq!(parse_range("bytes=-0", 10), Some(None));
+        assert_eq!(parse_range("bytes=0-1,3-4", 10), None);
+        assert_eq!(parse_range("bytes=4-1", 10), None);
+        assert_eq!(parse_range("items=0-1", 10), None);
+    }
+}
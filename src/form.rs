@@ -0,0 +1,242 @@
+use std::collections::{HashMap};
+use bytes::{Bytes};
+use serde::{ser, de};
+use serde_qs as qs;
+use super::{WriteHeader, AsTypePattern, CodecError, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody};
+
+// the form is not a subtype suffix so auto type uses this one only
+const FORM_TYPE: &str = "application/x-www-form-urlencoded";
+
+#[derive(Debug, Clone, Copy)]
+pub struct Form;
+
+impl BodyCodec for Form {
+    fn subtype(&self) -> &str {
+        "x-www-form-urlencoded"
+    }
+}
+
+impl<T> BodyDecoder<T> for Form
+    where for<'de> T: de::Deserialize<'de>
+{
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<T> {
+        qs::from_bytes(&data).map_err(|error| {
+            // the error kind is private in qs, so the well-formed data
+            // is detected by parsing it again without the target type,
+            // the error itself is not Sync and kept as message
+            if qs::from_bytes::<HashMap<String, de::IgnoredAny>>(&data).is_ok() {
+                CodecError::invalid_value(self.subtype())
+            } else {
+                CodecError::invalid_data(self.subtype())
+            }.with_source(error.to_string())
+        })
+    }
+}
+
+impl<T> BodyEncoder<T> for Form
+    where T: ser::Serialize
+{
+    fn encode_bytes(&self, data: T) -> CodecResult<Bytes> {
+        qs::to_string(&data).map(Bytes::from)
+            .map_err(|error| CodecError::encode_failed(self.subtype()).with_source(error.to_string()))
+    }
+}
+
+pub trait DecodeForm<T>: Sized {
+    fn decode_form(self) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeForm<T> for B
+    where B: DecodeData<T>,
+          for<'de> B::Data: de::Deserialize<'de>
+{
+    #[inline]
+    fn decode_form(self) -> CodecResult<T> {
+        self.decode_data(&Form)
+    }
+}
+
+pub trait DecodeFormBody<T>: DecodeForm<T>
+    where T: WriteHeader
+{
+    fn decode_form_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern;
+
+    fn decode_form_auto_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeFormBody<T> for B
+    where B: DecodeBody<T>,
+          T: WriteHeader,
+          for<'de> B::Data: de::Deserialize<'de>
+{
+    #[inline]
+    fn decode_form_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern
+    {
+        self.decode_body_with_type(&Form, mimetype)
+    }
+
+    #[inline]
+    fn decode_form_auto_type(self) -> CodecResult<T> {
+        self.decode_body_with_type(&Form, FORM_TYPE)
+    }
+}
+
+pub trait EncodeForm<T>: Sized {
+    fn encode_form(self) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeForm<T> for B
+    where B: EncodeData<T>,
+          B::Data: ser::Serialize
+{
+    #[inline]
+    fn encode_form(self) -> CodecResult<T> {
+        self.encode_data(&Form)
+    }
+}
+
+pub trait EncodeFormBody<T>: EncodeForm<T>
+    where T: WriteHeader
+{
+    fn encode_form_with_type(self, mimetype: &'static str) -> CodecResult<T>;
+
+    fn encode_form_auto_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeFormBody<T> for B
+    where B: EncodeBody<T>,
+          T: WriteHeader,
+          B::Data: ser::Serialize
+{
+    #[inline]
+    fn encode_form_with_type(self, mimetype: &'static str) -> CodecResult<T> {
+        self.encode_body_with_type(&Form, mimetype)
+    }
+
+    #[inline]
+    fn encode_form_auto_type(self) -> CodecResult<T> {
+        self.encode_body_with_type(&Form, FORM_TYPE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, Response};
+    use super::super::{ReadHeader, CodecErrorKind};
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        zip: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        address: Address,
+    }
+
+    fn user() -> User {
+        User { name: "Alice".into(), address: Address { city: "Paris".into(), zip: 75001 } }
+    }
+
+    #[test]
+    fn test_decode_form_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .body("name=Alice&address[city]=Paris&address[zip]=75001".into())
+            .unwrap();
+        let d: Request<User> = a.decode_form().unwrap();
+
+        assert_eq!(d.into_body(), user());
+    }
+
+    #[test]
+    fn test_decode_form_err_value() {
+        let a: Request<Bytes> = Request::builder()
+            .body("name=Alice&address[city]=Paris&address[zip]=abc".into())
+            .unwrap();
+        let d: Result<Request<User>, _> = a.decode_form();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn test_decode_form_err_data() {
+        let a: Request<Bytes> = Request::builder()
+            .body("name=Alice&address[[city]=Paris&address[zip]=75001".into())
+            .unwrap();
+        let d: Result<Request<User>, _> = a.decode_form();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_form_with_type_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/x-www-form-urlencoded; charset=utf-8")
+            .body("name=Alice&address[city]=Paris&address[zip]=75001".into())
+            .unwrap();
+        let d: Request<User> = a.decode_form_with_type("application/x-www-form-urlencoded").unwrap();
+
+        assert_eq!(d.into_body(), user());
+    }
+
+    #[test]
+    fn test_decode_form_with_type_err_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/json")
+            .body("name=Alice&address[city]=Paris&address[zip]=75001".into())
+            .unwrap();
+        let d: Result<Request<User>, _> = a.decode_form_with_type("application/x-www-form-urlencoded");
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_form_with_type() {
+        let a = Response::builder()
+            .body(user())
+            .unwrap();
+        let d: Response<Bytes> = a.encode_form_with_type("application/x-www-form-urlencoded").unwrap();
+
+        assert!(d.is_header("Content-Type", "application/x-www-form-urlencoded"));
+
+        let d: Response<User> = d.decode_form().unwrap();
+
+        assert_eq!(d.into_body(), user());
+    }
+
+    #[test]
+    fn test_decode_form_auto_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("name=Alice&address[city]=Paris&address[zip]=75001".into())
+            .unwrap();
+        let d: Request<User> = a.decode_form_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "application/x-www-form-urlencoded"));
+        assert_eq!(d.into_body(), user());
+
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+x-www-form-urlencoded")
+            .body("name=Alice&address[city]=Paris&address[zip]=75001".into())
+            .unwrap();
+        let d: Result<Request<User>, _> = a.decode_form_auto_type();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_form_auto_type() {
+        let a = Response::builder()
+            .header("Content-Type", "application/vnd.literium.v1")
+            .body(user())
+            .unwrap();
+        let d: Response<Bytes> = a.encode_form_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "application/x-www-form-urlencoded"));
+    }
+}
@@ -55,6 +55,9 @@ pub trait WriteHeader {
     fn set_header<K, V>(&mut self, key: K, value: V)
         where K: IntoHeaderName,
               HeaderValue: From<V>;
+
+    fn remove_header<K>(&mut self, key: K) -> Option<HeaderValue>
+        where K: AsHeaderName;
 }
 
 impl<T> WriteHeader for Request<T> {
@@ -64,6 +67,12 @@ impl<T> WriteHeader for Request<T> {
     {
         self.headers_mut().insert(key, value.into());
     }
+
+    fn remove_header<K>(&mut self, key: K) -> Option<HeaderValue>
+        where K: AsHeaderName
+    {
+        self.headers_mut().remove(key)
+    }
 }
 
 impl<T> WriteHeader for Response<T> {
@@ -73,6 +82,12 @@ impl<T> WriteHeader for Response<T> {
     {
         self.headers_mut().insert(key, value.into());
     }
+
+    fn remove_header<K>(&mut self, key: K) -> Option<HeaderValue>
+        where K: AsHeaderName
+    {
+        self.headers_mut().remove(key)
+    }
 }
 
 /*
@@ -108,25 +123,22 @@ mod tests {
     
     #[test]
     fn test_is_header() {
-        assert_eq!(Request::builder()
+        assert!(!Request::builder()
                    .body(())
                    .unwrap()
-                   .is_header("Accept", "application/json"),
-                   false);
+                   .is_header("Accept", "application/json"));
 
-        assert_eq!(Request::builder()
+        assert!(!Request::builder()
                    .header("Accept", "text/html")
                    .body(())
                    .unwrap()
-                   .is_header("Accept", "application/json"),
-                   false);
+                   .is_header("Accept", "application/json"));
 
-        assert_eq!(Request::builder()
+        assert!(Request::builder()
                    .header("Accept", "application/json")
                    .body(())
                    .unwrap()
-                   .is_header("Accept", "application/json"),
-                   true);
+                   .is_header("Accept", "application/json"));
     }
 
     #[test]
@@ -0,0 +1,165 @@
+use bytes::{Bytes};
+use hexlib;
+use super::{WriteHeader, AsTypePattern, CodecError, CodecPosition, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hex;
+
+impl BodyCodec for Hex {
+    fn subtype(&self) -> &str {
+        "hex"
+    }
+}
+
+impl BodyDecoder<Bytes> for Hex {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        hexlib::decode(&data).map(Bytes::from).map_err(|error| {
+            let codec_error = CodecError::invalid_data(self.subtype());
+            match error {
+                hexlib::FromHexError::InvalidHexCharacter { index, .. } =>
+                    codec_error.with_position(CodecPosition::Offset(index)),
+                _ => codec_error,
+            }.with_source(error)
+        })
+    }
+}
+
+impl BodyEncoder<Bytes> for Hex {
+    fn encode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        Ok(hexlib::encode(&data).into())
+    }
+}
+
+pub trait DecodeHex: Sized {
+    fn decode_hex(self) -> CodecResult<Self>;
+}
+
+impl<B> DecodeHex for B
+    where B: DecodeData<B, Data = Bytes>
+{
+    #[inline]
+    fn decode_hex(self) -> CodecResult<Self> {
+        self.decode_data(&Hex)
+    }
+}
+
+pub trait DecodeHexBody: DecodeHex + WriteHeader
+{
+    fn decode_hex_with_type<V>(self, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern;
+
+    fn decode_hex_auto_type(self) -> CodecResult<Self>;
+}
+
+impl<B> DecodeHexBody for B
+    where B: DecodeBody<B, Data = Bytes> + WriteHeader
+{
+    #[inline]
+    fn decode_hex_with_type<V>(self, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern
+    {
+        self.decode_body_with_type(&Hex, mimetype)
+    }
+
+    #[inline]
+    fn decode_hex_auto_type(self) -> CodecResult<Self> {
+        self.decode_body_auto_type(&Hex)
+    }
+}
+
+pub trait EncodeHex: Sized {
+    fn encode_hex(self) -> CodecResult<Self>;
+}
+
+impl<B> EncodeHex for B
+    where B: EncodeData<B, Data = Bytes>
+{
+    #[inline]
+    fn encode_hex(self) -> CodecResult<Self> {
+        self.encode_data(&Hex)
+    }
+}
+
+pub trait EncodeHexBody: EncodeHex + WriteHeader
+{
+    fn encode_hex_with_type(self, mimetype: &'static str) -> CodecResult<Self>;
+
+    fn encode_hex_auto_type(self) -> CodecResult<Self>;
+}
+
+impl<B> EncodeHexBody for B
+    where B: EncodeBody<B, Data = Bytes> + WriteHeader
+{
+    #[inline]
+    fn encode_hex_with_type(self, mimetype: &'static str) -> CodecResult<Self> {
+        self.encode_body_with_type(&Hex, mimetype)
+    }
+
+    #[inline]
+    fn encode_hex_auto_type(self) -> CodecResult<Self> {
+        self.encode_body_auto_type(&Hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, Response};
+    use super::super::{ReadHeader, CodecErrorKind, CodecPosition};
+    use super::*;
+
+    #[test]
+    fn test_decode_hex_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .body("68656c6c6f20776f726c64".into())
+            .unwrap();
+        let d = a.decode_hex().unwrap();
+        
+        assert_eq!(d.into_body(), "hello world");
+    }
+
+    #[test]
+    fn test_decode_hex_err_data() {
+        let a: Request<Bytes> = Request::builder()
+            .body("68656c6c6g20776f726c64".into())
+            .unwrap();
+        let e = a.decode_hex().unwrap_err();
+        
+        assert_eq!(e.kind(), CodecErrorKind::InvalidData);
+        assert_eq!(e.position(), Some(CodecPosition::Offset(9)));
+    }
+
+    #[test]
+    fn test_decode_hex_auto_type_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/octet-stream+hex")
+            .body("68656C6C6F20776F726C64".into())
+            .unwrap();
+        let d = a.decode_hex_auto_type().unwrap();
+        
+        assert!(d.is_header("Content-Type", "application/octet-stream"));
+        assert_eq!(d.into_body(), "hello world");
+    }
+
+    #[test]
+    fn test_decode_hex_auto_type_err_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/octet-stream+base64")
+            .body("68656c6c6f20776f726c64".into())
+            .unwrap();
+        let d = a.decode_hex_auto_type();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_hex_auto_type() {
+        let a: Response<Bytes> = Response::builder()
+            .header("Content-Type", "application/octet-stream")
+            .body("hello world".into())
+            .unwrap();
+        let d = a.encode_hex_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "application/octet-stream+hex"));
+        assert_eq!(d.into_body(), "68656c6c6f20776f726c64");
+    }
+}
@@ -1,153 +1,133 @@
-use http::{Request, Response};
-use http::header::{HeaderValue};
 use bytes::{Bytes};
 use serde::{ser, de};
 use serde_json as json;
-use super::{ReadHeader, WriteHeader, CodecError, CodecResult, UnwrapType, WrapType};
+use super::{WriteHeader, AsTypePattern, CodecError, CodecPosition, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody};
 
-pub trait DecodeJson<T>: Sized {
-    fn decode_json_native(self) -> json::Result<T>;
-    
-    #[inline]
-    fn decode_json(self) -> CodecResult<T> {
-        self.decode_json_native().map_err(|_| CodecError::InvalidData)
-    }
-}
+#[derive(Debug, Clone, Copy)]
+pub struct Json;
 
-pub trait DecodeJsonBody<T>: DecodeJson<T> + ReadHeader + UnwrapType
-    where T: WriteHeader
-{
-    #[inline]
-    fn decode_json_with_type<V>(self, mimetype: V) -> CodecResult<T>
-        where HeaderValue: PartialEq<V>
-    {
-        if self.is_header("Content-Type", mimetype) {
-            self.decode_json()
-        } else {
-            Err(CodecError::InvalidType)
-        }
-    }
-
-    #[inline]
-    fn decode_json_auto_type(self) -> CodecResult<T> {
-        if let Some(mimetype) = self.unwrap_type("json") {
-            self.decode_json().map(move |mut new_self| {
-                new_self.set_header("Content-Type", mimetype);
-                new_self
-            })
-        } else {
-            Err(CodecError::InvalidType)
-        }
+impl BodyCodec for Json {
+    fn subtype(&self) -> &str {
+        "json"
     }
 }
 
-impl<T> DecodeJson<T> for Bytes
+impl<T> BodyDecoder<T> for Json
     where for<'de> T: de::Deserialize<'de>
 {
-    fn decode_json_native(self) -> json::Result<T> {
-        json::from_slice(&self)
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<T> {
+        json::from_slice(&data).map_err(|error| {
+            // value errors may hide syntax errors which follows
+            let error = if error.is_data() {
+                json::from_slice::<de::IgnoredAny>(&data).err().unwrap_or(error)
+            } else {
+                error
+            };
+            let position = CodecPosition::LineColumn(error.line(), error.column());
+            if error.is_data() {
+                CodecError::invalid_value(self.subtype())
+            } else {
+                CodecError::invalid_data(self.subtype())
+            }.with_position(position).with_source(error)
+        })
     }
 }
 
-impl<T> DecodeJson<Request<T>> for Request<Bytes>
-    where for<'de> T: de::Deserialize<'de>
+impl<T> BodyEncoder<T> for Json
+    where T: ser::Serialize
 {
-    fn decode_json_native(self) -> json::Result<Request<T>> {
-        let (parts, body) = self.into_parts();
-        let body = body.decode_json_native()?;
-        Ok(Request::from_parts(parts, body))
+    fn encode_bytes(&self, data: T) -> CodecResult<Bytes> {
+        json::to_vec(&data).map(Bytes::from)
+            .map_err(|error| CodecError::encode_failed(self.subtype()).with_source(error))
     }
 }
 
-impl<T> DecodeJsonBody<Request<T>> for Request<Bytes>
-    where for<'de> T: de::Deserialize<'de> {}
+pub trait DecodeJson<T>: Sized {
+    fn decode_json(self) -> CodecResult<T>;
+}
 
-impl<T> DecodeJson<Response<T>> for Response<Bytes>
-    where for<'de> T: de::Deserialize<'de>
+impl<T, B> DecodeJson<T> for B
+    where B: DecodeData<T>,
+          for<'de> B::Data: de::Deserialize<'de>
 {
-    fn decode_json_native(self) -> json::Result<Response<T>> {
-        let (parts, body) = self.into_parts();
-        let body = body.decode_json_native()?;
-        Ok(Response::from_parts(parts, body))
+    #[inline]
+    fn decode_json(self) -> CodecResult<T> {
+        self.decode_data(&Json)
     }
 }
 
-impl<T> DecodeJsonBody<Response<T>> for Response<Bytes>
-    where for<'de> T: de::Deserialize<'de> {}
-
-pub trait EncodeJson<T>: Sized {
-    fn encode_json_native(self) -> json::Result<T>;
+pub trait DecodeJsonBody<T>: DecodeJson<T>
+    where T: WriteHeader
+{
+    fn decode_json_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern;
 
-    #[inline]
-    fn encode_json(self) -> CodecResult<T> {
-        self.encode_json_native().map_err(|_| CodecError::InvalidData)
-    }
+    fn decode_json_auto_type(self) -> CodecResult<T>;
 }
 
-pub trait EncodeJsonBody<T>: EncodeJson<T> + ReadHeader + WrapType
-    where T: WriteHeader
+impl<T, B> DecodeJsonBody<T> for B
+    where B: DecodeBody<T>,
+          T: WriteHeader,
+          for<'de> B::Data: de::Deserialize<'de>
 {
     #[inline]
-    fn encode_json_with_type(self, mimetype: &'static str) -> CodecResult<T>
+    fn decode_json_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern
     {
-        self.encode_json()
-            .map(|mut new_self| {
-                new_self.set_header("Content-Type", HeaderValue::from_static(mimetype));
-                new_self
-            })
+        self.decode_body_with_type(&Json, mimetype)
     }
 
     #[inline]
-    fn encode_json_auto_type(self) -> CodecResult<T> {
-        if let Some(mimetype) = self.wrap_type("json") {
-            self.encode_json()
-                .map(move |mut new_self| {
-                    new_self.set_header("Content-Type", mimetype);
-                    new_self
-                })
-        } else {
-            Err(CodecError::InvalidType)
-        }
+    fn decode_json_auto_type(self) -> CodecResult<T> {
+        self.decode_body_auto_type(&Json)
     }
 }
 
-impl<T> EncodeJson<Bytes> for T
-    where T: ser::Serialize
-{
-    fn encode_json_native(self) -> json::Result<Bytes> {
-        Ok(json::to_vec(&self)?.into())
-    }
+pub trait EncodeJson<T>: Sized {
+    fn encode_json(self) -> CodecResult<T>;
 }
 
-impl<T> EncodeJson<Request<Bytes>> for Request<T>
-    where T: ser::Serialize
+impl<T, B> EncodeJson<T> for B
+    where B: EncodeData<T>,
+          B::Data: ser::Serialize
 {
-    fn encode_json_native(self) -> json::Result<Request<Bytes>> {
-        let (parts, body) = self.into_parts();
-        let body = body.encode_json_native()?;
-        Ok(Request::from_parts(parts, body))
+    #[inline]
+    fn encode_json(self) -> CodecResult<T> {
+        self.encode_data(&Json)
     }
 }
 
-impl<T> EncodeJsonBody<Request<Bytes>> for Request<T>
-    where T: ser::Serialize {}
+pub trait EncodeJsonBody<T>: EncodeJson<T>
+    where T: WriteHeader
+{
+    fn encode_json_with_type(self, mimetype: &'static str) -> CodecResult<T>;
 
-impl<T> EncodeJson<Response<Bytes>> for Response<T>
-    where T: ser::Serialize
+    fn encode_json_auto_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeJsonBody<T> for B
+    where B: EncodeBody<T>,
+          T: WriteHeader,
+          B::Data: ser::Serialize
 {
-    fn encode_json_native(self) -> json::Result<Response<Bytes>> {
-        let (parts, body) = self.into_parts();
-        let body = body.encode_json_native()?;
-        Ok(Response::from_parts(parts, body))
+    #[inline]
+    fn encode_json_with_type(self, mimetype: &'static str) -> CodecResult<T> {
+        self.encode_body_with_type(&Json, mimetype)
     }
-}
 
-impl<T> EncodeJsonBody<Response<Bytes>> for Response<T>
-    where T: ser::Serialize {}
+    #[inline]
+    fn encode_json_auto_type(self) -> CodecResult<T> {
+        self.encode_body_auto_type(&Json)
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{HashMap};
     use http::{Request, Response};
+    use http::{StatusCode};
+    use http::header::{HeaderValue};
+    use super::super::{ReadHeader, CodecErrorKind};
     use super::*;
 
     #[test]
@@ -167,7 +147,21 @@ mod tests {
             .unwrap();
         let d: Result<Request<Vec<u8>>, _> = a.decode_json();
         
-        assert_eq!(d.unwrap_err(), CodecError::InvalidData);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_json_err_value() {
+        let a: Request<Bytes> = Request::builder()
+            .body("[13,1,\"0\"]".into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_json();
+        let e = d.unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidValue);
+        assert_eq!(e.codec(), Some("json"));
+        assert_eq!(e.position(), Some(CodecPosition::LineColumn(1, 9)));
+        assert_eq!(e.status(), StatusCode::UNPROCESSABLE_ENTITY);
     }
 
     #[test]
@@ -189,7 +183,7 @@ mod tests {
             .unwrap();
         let d: Result<Request<Vec<u8>>, _> = a.decode_json_with_type("application/json");
         
-        assert_eq!(d.unwrap_err(), CodecError::InvalidData);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
     }
 
     #[test]
@@ -200,7 +194,38 @@ mod tests {
             .unwrap();
         let d: Result<Request<Vec<u8>>, _> = a.decode_json_with_type("application/json");
         
-        assert_eq!(d.unwrap_err(), CodecError::InvalidType);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_decode_json_with_type_pattern() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "Application/Problem+JSON; charset=utf-8")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let d: Request<Vec<u8>> = a.decode_json_with_type("application/*+json").unwrap();
+
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
+    #[test]
+    fn test_decode_json_with_type_header_value() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/json")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let mimetype = HeaderValue::from_static("application/json");
+        let d: Request<Vec<u8>> = a.decode_json_with_type(&mimetype).unwrap();
+
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/json")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_json_with_type(HeaderValue::from_static("application/cbor"));
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
     }
 
     #[test]
@@ -214,6 +239,18 @@ mod tests {
         assert!(d.is_header("Content-Type", "application/vnd.literium.v1"));
     }
 
+    #[test]
+    fn test_decode_json_auto_type_params() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+json; charset=utf-8")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let d: Request<Vec<u8>> = a.decode_json_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1; charset=utf-8"));
+        assert_eq!(d.into_body(), vec![13u8, 1, 0]);
+    }
+
     #[test]
     fn test_decode_json_auto_type_err_data() {
         let a: Request<Bytes> = Request::builder()
@@ -222,7 +259,7 @@ mod tests {
             .unwrap();
         let d: Result<Request<Vec<u8>>, _> = a.decode_json_auto_type();
 
-        assert_eq!(d.unwrap_err(), CodecError::InvalidData);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
     }
 
     #[test]
@@ -233,7 +270,18 @@ mod tests {
             .unwrap();
         let d: Result<Request<Vec<u8>>, _> = a.decode_json_auto_type();
 
-        assert_eq!(d.unwrap_err(), CodecError::InvalidType);
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_decode_json_auto_type_err_malformed() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/+json")
+            .body("[13,1,0]".into())
+            .unwrap();
+        let d: Result<Request<Vec<u8>>, _> = a.decode_json_auto_type();
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidType);
     }
 
     #[test]
@@ -246,6 +294,19 @@ mod tests {
         assert_eq!(d.into_body(), "[13,1,0]");
     }
 
+    #[test]
+    fn test_encode_json_err() {
+        let mut m = HashMap::new();
+        m.insert((1u8, 2u8), 3u8);
+        let a = Response::builder()
+            .body(m)
+            .unwrap();
+        let e = a.encode_json().map(|_: Response<Bytes>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::EncodeFailed);
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
     #[test]
     fn test_encode_json_with_type() {
         let a = Response::builder()
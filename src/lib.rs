@@ -1,38 +1,74 @@
 extern crate futures;
+extern crate tokio_threadpool;
 extern crate http;
 extern crate hyper;
 extern crate bytes;
 extern crate serde;
 extern crate serde_json;
+extern crate serde_cbor;
 extern crate serde_qs;
 extern crate base64 as base64lib;
+extern crate hex as hexlib;
 extern crate sodiumoxide;
+extern crate url;
+extern crate time;
+#[cfg(test)]
+#[macro_use]
+extern crate serde_derive;
 
 mod query;
+mod query_path;
+mod query_list;
 mod header;
 mod client_info;
 mod content_type;
 mod types_chain;
+mod accept;
 mod route;
+mod router;
+mod files;
 mod body;
 mod codec;
 mod json;
+mod cbor;
+mod form;
+mod multipart;
+mod text;
 mod base64;
+mod base64_stream;
+mod hex;
 mod crypto;
 mod sealedbox;
+mod secretbox;
+mod cryptobox;
+mod chain;
 mod binary;
 pub mod serde_base64;
+pub mod serde_base64url;
+pub mod serde_hex;
 
 pub use query::*;
 pub use header::*;
 pub use client_info::*;
 pub use content_type::*;
 pub use types_chain::*;
+pub use accept::*;
 pub use route::*;
+pub use router::*;
+pub use files::*;
 pub use body::*;
 pub use codec::*;
 pub use json::*;
+pub use cbor::*;
+pub use form::*;
+pub use multipart::*;
+pub use text::*;
 pub use base64::*;
+pub use base64_stream::*;
+pub use hex::*;
 pub use crypto::*;
 pub use sealedbox::*;
+pub use secretbox::*;
+pub use cryptobox::*;
+pub use chain::*;
 pub use binary::*;
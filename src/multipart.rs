@@ -0,0 +1,564 @@
+use std::{error, fmt};
+use std::sync::{Arc, Mutex, MutexGuard};
+use futures::{Future, Stream, Poll, Async};
+use http::{Request};
+use http::request::{Parts as RequestParts};
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use bytes::{Bytes, BytesMut};
+use hyper::{Body, Error};
+use serde::{de};
+use serde_qs as qs;
+use url::form_urlencoded;
+use url::percent_encoding::{percent_decode};
+use super::{ReadHeader, UnwrapBody, WrapBody, ContentType, CodecError, CodecResult};
+
+// the limit of part headers block
+const MAX_HEADERS_SIZE: usize = 8192;
+
+#[derive(Debug)]
+pub enum MultipartError {
+    Transport(Error),
+    Malformed(&'static str),
+    TooLarge,
+    InvalidValue(String),
+    // the shared state is poisoned by panic while reading
+    Poisoned,
+}
+
+impl From<Error> for MultipartError {
+    fn from(error: Error) -> Self {
+        MultipartError::Transport(error)
+    }
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MultipartError::Transport(error) => write!(f, "Transport error: {}", error),
+            MultipartError::Malformed(reason) => write!(f, "Malformed multipart: {}", reason),
+            MultipartError::TooLarge => f.write_str("Multipart too large"),
+            MultipartError::InvalidValue(reason) => write!(f, "Invalid value: {}", reason),
+            MultipartError::Poisoned => f.write_str("Multipart state poisoned"),
+        }
+    }
+}
+
+impl error::Error for MultipartError {}
+
+pub type MultipartFuture<T> = Box<dyn Future<Item = T, Error = MultipartError> + Send>;
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|window| window == pattern)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Body,
+    Delimiter,
+    Headers,
+    End,
+}
+
+struct Inner {
+    body: Body,
+    delimiter: Bytes,
+    buffer: BytesMut,
+    state: State,
+    // index of current part, zero means preamble
+    part: usize,
+    part_size: usize,
+    part_limit: Option<usize>,
+    total_size: usize,
+    total_limit: Option<usize>,
+}
+
+impl Inner {
+    fn fill(&mut self) -> Poll<bool, MultipartError> {
+        match self.body.poll()? {
+            Async::NotReady => Ok(Async::NotReady),
+            Async::Ready(None) => Ok(Async::Ready(false)),
+            Async::Ready(Some(chunk)) => {
+                self.total_size += chunk.len();
+                if self.total_limit.map(|limit| self.total_size > limit).unwrap_or(false) {
+                    return Err(MultipartError::TooLarge);
+                }
+                self.buffer.extend_from_slice(&chunk);
+                Ok(Async::Ready(true))
+            },
+        }
+    }
+
+    fn take_data(&mut self, len: usize) -> Poll<Option<Bytes>, MultipartError> {
+        self.part_size += len;
+        if self.part > 0 && self.part_limit.map(|limit| self.part_size > limit).unwrap_or(false) {
+            return Err(MultipartError::TooLarge);
+        }
+        Ok(Async::Ready(Some(self.buffer.split_to(len).freeze())))
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Bytes>, MultipartError> {
+        loop {
+            if self.state != State::Body {
+                return Ok(Async::Ready(None));
+            }
+            if let Some(pos) = find(&self.buffer, &self.delimiter) {
+                self.state = State::Delimiter;
+                if pos > 0 {
+                    return self.take_data(pos);
+                }
+                return Ok(Async::Ready(None));
+            }
+            // the tail of buffer may be a beginning of delimiter
+            let keep = self.delimiter.len() - 1;
+            if self.buffer.len() > keep {
+                let len = self.buffer.len() - keep;
+                return self.take_data(len);
+            }
+            match self.fill()? {
+                Async::NotReady => return Ok(Async::NotReady),
+                Async::Ready(false) => return Err(MultipartError::Malformed("Unexpected end of stream")),
+                Async::Ready(true) => (),
+            }
+        }
+    }
+
+    fn parse_headers(data: &[u8]) -> Result<HeaderMap, MultipartError> {
+        let mut headers = HeaderMap::new();
+        for line in data.split(|chr| *chr == b'\n') {
+            let line = if line.ends_with(b"\r") { &line[..line.len() - 1] } else { line };
+            let pos = line.iter().position(|chr| *chr == b':')
+                .ok_or(MultipartError::Malformed("Invalid part header"))?;
+            let name = HeaderName::from_bytes(&line[..pos])
+                .map_err(|_| MultipartError::Malformed("Invalid part header name"))?;
+            let value = HeaderValue::from_bytes(trim(&line[pos + 1..]))
+                .map_err(|_| MultipartError::Malformed("Invalid part header value"))?;
+            headers.append(name, value);
+        }
+        Ok(headers)
+    }
+
+    fn poll_part(&mut self) -> Poll<Option<HeaderMap>, MultipartError> {
+        loop {
+            match self.state {
+                // skip preamble or the rest of previous part
+                State::Body => match self.poll_data()? {
+                    Async::NotReady => return Ok(Async::NotReady),
+                    _ => continue,
+                },
+                // the delimiter is followed by "--" for last part or CRLF
+                State::Delimiter => {
+                    let len = self.delimiter.len() + 2;
+                    if self.buffer.len() >= len {
+                        let delimiter = self.buffer.split_to(len);
+                        self.state = match &delimiter[len - 2..] {
+                            b"--" => State::End,
+                            b"\r\n" => State::Headers,
+                            _ => return Err(MultipartError::Malformed("Invalid delimiter")),
+                        };
+                        continue;
+                    }
+                },
+                State::Headers => {
+                    if self.buffer.starts_with(b"\r\n") {
+                        self.buffer.advance(2);
+                        return Ok(Async::Ready(Some(self.start_part(HeaderMap::new()))));
+                    }
+                    if let Some(pos) = find(&self.buffer, b"\r\n\r\n") {
+                        let headers = Self::parse_headers(&self.buffer[..pos])?;
+                        self.buffer.advance(pos + 4);
+                        return Ok(Async::Ready(Some(self.start_part(headers))));
+                    }
+                    if self.buffer.len() > MAX_HEADERS_SIZE {
+                        return Err(MultipartError::TooLarge);
+                    }
+                },
+                // the epilogue is ignored
+                State::End => return Ok(Async::Ready(None)),
+            }
+            match self.fill()? {
+                Async::NotReady => return Ok(Async::NotReady),
+                Async::Ready(false) => return Err(MultipartError::Malformed("Unexpected end of stream")),
+                Async::Ready(true) => (),
+            }
+        }
+    }
+
+    fn start_part(&mut self, headers: HeaderMap) -> HeaderMap {
+        self.state = State::Body;
+        self.part += 1;
+        self.part_size = 0;
+        headers
+    }
+}
+
+fn trim(data: &[u8]) -> &[u8] {
+    let start = data.iter().position(|chr| !chr.is_ascii_whitespace()).unwrap_or(data.len());
+    let end = data.iter().rposition(|chr| !chr.is_ascii_whitespace()).map(|pos| pos + 1).unwrap_or(start);
+    &data[start..end]
+}
+
+fn lock(inner: &Mutex<Inner>) -> Result<MutexGuard<'_, Inner>, MultipartError> {
+    inner.lock().map_err(|_| MultipartError::Poisoned)
+}
+
+pub struct Multipart {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Multipart {
+    pub fn new(body: Body, boundary: &str, part_limit: Option<usize>, total_limit: Option<usize>) -> Self {
+        let mut delimiter = BytesMut::with_capacity(boundary.len() + 4);
+        delimiter.extend_from_slice(b"\r\n--");
+        delimiter.extend_from_slice(boundary.as_bytes());
+        // the first delimiter has no leading CRLF
+        let buffer = BytesMut::from(&b"\r\n"[..]);
+        Multipart {
+            inner: Arc::new(Mutex::new(Inner {
+                body,
+                delimiter: delimiter.freeze(),
+                buffer,
+                state: State::Body,
+                part: 0,
+                part_size: 0,
+                part_limit,
+                total_size: 0,
+                total_limit,
+            })),
+        }
+    }
+
+    // collects text fields and deserializes it like a form,
+    // file fields which has filename are skipped
+    pub fn collect_fields<T>(self) -> MultipartFuture<T>
+        where for<'de> T: de::Deserialize<'de> + Send + 'static
+    {
+        Box::new(self.filter(|part| part.filename().is_none())
+                 .and_then(|part| {
+                     let name = part.name().unwrap_or("").to_string();
+                     part.into_body().concat2().and_then(move |data| {
+                         String::from_utf8(data.to_vec())
+                             .map(|data| (name, data))
+                             .map_err(|_| MultipartError::Malformed("Invalid utf-8 field"))
+                     })
+                 })
+                 .fold(String::new(), |mut query, (name, value)| {
+                     if !query.is_empty() {
+                         query.push('&');
+                     }
+                     // the brackets in name are kept to support nested fields
+                     let name: String = form_urlencoded::byte_serialize(name.as_bytes()).collect();
+                     query.push_str(&name.replace("%5B", "[").replace("%5D", "]"));
+                     query.push('=');
+                     query.extend(form_urlencoded::byte_serialize(value.as_bytes()));
+                     Ok::<_, MultipartError>(query)
+                 })
+                 .and_then(|query| qs::from_str(&query)
+                           .map_err(|error| MultipartError::InvalidValue(error.to_string()))))
+    }
+}
+
+impl Stream for Multipart {
+    type Item = Part;
+    type Error = MultipartError;
+
+    fn poll(&mut self) -> Poll<Option<Part>, MultipartError> {
+        let mut inner = lock(&self.inner)?;
+        Ok(match inner.poll_part()? {
+            Async::NotReady => Async::NotReady,
+            Async::Ready(None) => Async::Ready(None),
+            Async::Ready(Some(headers)) => Async::Ready(Some(Part::new(headers, PartBody {
+                inner: self.inner.clone(),
+                part: inner.part,
+            }))),
+        })
+    }
+}
+
+// the extended value like `UTF-8''%E2%82%AC.txt` (RFC 5987)
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let data: Vec<u8> = percent_decode(parts.next()?.as_bytes()).collect();
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(data).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(data.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+// the extended param like `filename*` takes precedence
+fn get_disposition_param(ct: &ContentType, name: &str) -> Option<String> {
+    ct.get_param(&format!("{}*", name))
+        .and_then(decode_ext_value)
+        .or_else(|| ct.get_param(name).map(String::from))
+}
+
+pub struct Part {
+    headers: HeaderMap,
+    name: Option<String>,
+    filename: Option<String>,
+    body: PartBody,
+}
+
+impl Part {
+    fn new(headers: HeaderMap, body: PartBody) -> Self {
+        // the disposition has same syntax as media type with parameters,
+        // the non-ascii names is sent as raw utf-8 by browsers
+        let (name, filename) = headers.get("Content-Disposition")
+            .map(|value| ContentType::new(String::from_utf8_lossy(value.as_bytes())))
+            .map(|ct| (get_disposition_param(&ct, "name"),
+                       get_disposition_param(&ct, "filename")))
+            .unwrap_or((None, None));
+        Part { headers, name, filename, body }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.get_header_str("Content-Type")
+    }
+
+    pub fn into_body(self) -> PartBody {
+        self.body
+    }
+}
+
+impl ReadHeader for Part {
+    fn get_headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+pub struct PartBody {
+    inner: Arc<Mutex<Inner>>,
+    part: usize,
+}
+
+impl Stream for PartBody {
+    type Item = Bytes;
+    type Error = MultipartError;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, MultipartError> {
+        let mut inner = lock(&self.inner)?;
+        // the body of skipped part is not available anymore
+        if inner.part != self.part {
+            return Ok(Async::Ready(None));
+        }
+        inner.poll_data()
+    }
+}
+
+pub trait ReadMultipart<T, H>: UnwrapBody<H, Body> + ReadHeader + Sized
+    where T: WrapBody<H, Multipart>
+{
+    fn multipart_limited(self, part_limit: Option<usize>, total_limit: Option<usize>) -> CodecResult<T> {
+        let boundary = {
+            let ct = self.get_header_str("Content-Type")
+                .ok_or_else(|| CodecError::invalid_type("form-data"))
+                .and_then(|mimetype| ContentType::parse(mimetype)
+                          .map_err(|error| CodecError::invalid_type("form-data").with_source(error)))?;
+            if !ct.matches(&ContentType::new("multipart/form-data")) {
+                return Err(CodecError::invalid_type("form-data"));
+            }
+            match ct.get_param("boundary") {
+                Some(boundary) if !boundary.is_empty() && boundary.len() <= 70 => boundary.to_string(),
+                _ => return Err(CodecError::invalid_type("form-data")
+                                .with_source("Missing or invalid boundary")),
+            }
+        };
+        let (parts, body) = self.unwrap_body();
+        Ok(T::wrap_body(parts, Multipart::new(body, &boundary, part_limit, total_limit)))
+    }
+
+    fn multipart(self) -> CodecResult<T> {
+        self.multipart_limited(None, None)
+    }
+
+    fn multipart_with_limits(self, part_limit: usize, total_limit: usize) -> CodecResult<T> {
+        self.multipart_limited(Some(part_limit), Some(total_limit))
+    }
+}
+
+impl ReadMultipart<Request<Multipart>, RequestParts> for Request<Body> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use futures::stream::{iter_ok};
+    use super::super::{CodecErrorKind};
+    use super::*;
+
+    const BODY: &str = "preamble\r\n\
+                        --XyZ\r\n\
+                        Content-Disposition: form-data; name=\"user[name]\"\r\n\
+                        \r\n\
+                        Alice\r\n\
+                        --XyZ\r\n\
+                        Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+                        Content-Type: text/plain\r\n\
+                        \r\n\
+                        hello\r\n--XyY world\r\n\
+                        --XyZ\r\n\
+                        Content-Disposition: form-data; name=\"user[age]\"\r\n\
+                        \r\n\
+                        33\r\n\
+                        --XyZ--\r\n\
+                        epilogue";
+
+    fn request(chunk: usize) -> Request<Body> {
+        let chunks: Vec<Vec<u8>> = BODY.as_bytes().chunks(chunk).map(Vec::from).collect();
+        Request::builder()
+            .header("Content-Type", "multipart/form-data; boundary=XyZ")
+            .body(Body::wrap_stream(iter_ok::<_, io::Error>(chunks)))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_multipart() {
+        for chunk in &[1, 3, 7, 1024] {
+            let m = request(*chunk).multipart().unwrap().into_body();
+            let parts = m.and_then(|part| {
+                let name = part.name().map(String::from);
+                let filename = part.filename().map(String::from);
+                let content_type = part.content_type().map(String::from);
+                part.into_body().concat2().map(move |data| (name, filename, content_type, data))
+            }).collect().wait().unwrap();
+
+            assert_eq!(parts.len(), 3);
+            assert_eq!(parts[0].0, Some("user[name]".into()));
+            assert_eq!(parts[0].3, "Alice");
+            assert_eq!(parts[1].0, Some("file".into()));
+            assert_eq!(parts[1].1, Some("a.txt".into()));
+            assert_eq!(parts[1].2, Some("text/plain".into()));
+            assert_eq!(parts[1].3, "hello\r\n--XyY world");
+            assert_eq!(parts[2].3, "33");
+        }
+    }
+
+    #[test]
+    fn test_multipart_skip_parts() {
+        let m = request(5).multipart().unwrap().into_body();
+        let names = m.map(|part| part.name().map(String::from)).collect().wait().unwrap();
+
+        assert_eq!(names, vec![Some("user[name]".into()), Some("file".into()), Some("user[age]".into())]);
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct User {
+        name: String,
+        age: u32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Fields {
+        user: User,
+    }
+
+    #[test]
+    fn test_multipart_collect_fields() {
+        let f: Fields = request(4).multipart().unwrap().into_body()
+            .collect_fields().wait().unwrap();
+
+        assert_eq!(f, Fields { user: User { name: "Alice".into(), age: 33 } });
+    }
+
+    #[test]
+    fn test_multipart_non_ascii_filename() {
+        let mut body = Vec::new();
+        body.extend_from_slice("--XyZ\r\n\
+                                Content-Disposition: form-data; name=\"user[name]\"\r\n\
+                                \r\n\
+                                Алиса\r\n\
+                                --XyZ\r\n\
+                                Content-Disposition: form-data; name=\"photo\"; filename=\"фото.bin\"\r\n\
+                                \r\n".as_bytes());
+        body.extend_from_slice(&[0xff, 0xfe, 0x00]);
+        body.extend_from_slice(b"\r\n--XyZ\r\n\
+                                 Content-Disposition: form-data; name=\"doc\"; filename=\"EUR.txt\"; \
+                                 filename*=UTF-8''%E2%82%AC%20rates.txt\r\n\
+                                 \r\n\
+                                 1.1\r\n\
+                                 --XyZ\r\n\
+                                 Content-Disposition: form-data; name=\"user[age]\"\r\n\
+                                 \r\n\
+                                 33\r\n\
+                                 --XyZ--\r\n");
+        let request = || Request::builder()
+            .header("Content-Type", "multipart/form-data; boundary=XyZ")
+            .body(Body::from(body.clone()))
+            .unwrap();
+
+        let filenames = request().multipart().unwrap().into_body()
+            .map(|part| part.filename().map(String::from)).collect().wait().unwrap();
+
+        assert_eq!(filenames, vec![None, Some("фото.bin".into()), Some("€ rates.txt".into()), None]);
+
+        let f: Fields = request().multipart().unwrap().into_body()
+            .collect_fields().wait().unwrap();
+
+        assert_eq!(f, Fields { user: User { name: "Алиса".into(), age: 33 } });
+    }
+
+    #[test]
+    fn test_multipart_err_part_limit() {
+        let m = request(16).multipart_with_limits(10, 1024).unwrap().into_body();
+        let r = m.and_then(|part| part.into_body().concat2()).collect().wait();
+
+        match r {
+            Err(MultipartError::TooLarge) => (),
+            _ => panic!("Expected TooLarge error"),
+        }
+    }
+
+    #[test]
+    fn test_multipart_err_total_limit() {
+        let m = request(16).multipart_with_limits(1024, 100).unwrap().into_body();
+        let r = m.collect().wait();
+
+        match r {
+            Err(MultipartError::TooLarge) => (),
+            _ => panic!("Expected TooLarge error"),
+        }
+    }
+
+    #[test]
+    fn test_multipart_err_malformed() {
+        let a = Request::builder()
+            .header("Content-Type", "multipart/form-data; boundary=XyZ")
+            .body(Body::from("--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nAlice"))
+            .unwrap();
+        let r = a.multipart().unwrap().into_body()
+            .and_then(|part| part.into_body().concat2()).collect().wait();
+
+        match r {
+            Err(MultipartError::Malformed(_)) => (),
+            _ => panic!("Expected Malformed error"),
+        }
+    }
+
+    #[test]
+    fn test_multipart_err_type() {
+        let a = Request::builder()
+            .header("Content-Type", "multipart/form-data")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(a.multipart().err().unwrap().kind(), CodecErrorKind::InvalidType);
+
+        let a = Request::builder()
+            .header("Content-Type", "application/json; boundary=XyZ")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(a.multipart().err().unwrap().kind(), CodecErrorKind::InvalidType);
+    }
+}
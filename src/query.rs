@@ -1,6 +1,99 @@
-use http::{Request};
-use serde::{de};
+use std::{error, fmt};
+use std::collections::{HashSet};
+use http::{self, Request, Uri, StatusCode};
+use http::uri::{PathAndQuery};
+use bytes::{Bytes};
+use serde::{ser, de};
 use serde_qs as qs;
+use url::form_urlencoded;
+use super::query_path::{Tracked};
+use super::query_list;
+
+#[derive(Debug)]
+pub enum QueryError {
+    Format(qs::Error),
+    // the path of field and the reason
+    Field(String, String),
+    Uri(http::Error),
+}
+
+impl QueryError {
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            QueryError::Field(path, _) if !path.is_empty() => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            QueryError::Uri(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl From<qs::Error> for QueryError {
+    fn from(error: qs::Error) -> Self {
+        QueryError::Format(error)
+    }
+}
+
+impl From<http::Error> for QueryError {
+    fn from(error: http::Error) -> Self {
+        QueryError::Uri(error)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::Format(error) => write!(f, "Query format error: {}", error),
+            QueryError::Field(path, reason) if path.is_empty() => write!(f, "Invalid query: {}", reason),
+            QueryError::Field(path, reason) => write!(f, "Invalid query field {}: {}", path, reason),
+            QueryError::Uri(error) => write!(f, "Invalid URI: {}", error),
+        }
+    }
+}
+
+impl error::Error for QueryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryConfig {
+    max_depth: usize,
+    strict: bool,
+}
+
+// same as defaults of qs
+impl Default for QueryConfig {
+    fn default() -> Self {
+        QueryConfig { max_depth: 5, strict: true }
+    }
+}
+
+impl QueryConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the deeper nesting is kept as flat keys
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    // the lenient mode accepts percent-encoded brackets and ignores malformed ones
+    pub fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+}
 
 pub trait ReadQuery {
     fn get_query_str(&self) -> Option<&str>;
@@ -8,7 +101,36 @@ pub trait ReadQuery {
         where for<'de> T: de::Deserialize<'de>
     {
         if let Some(q) = self.get_query_str() {
-            qs::from_str(q)
+            qs::from_str(q).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_query_with<T>(&self, config: &QueryConfig) -> Result<Option<T>, QueryError>
+        where for<'de> T: de::Deserialize<'de>
+    {
+        if let Some(q) = self.get_query_str() {
+            match qs::Config::new(config.max_depth, config.strict).deserialize_str(q)? {
+                Tracked::Value(value) => Ok(Some(value)),
+                Tracked::Error(path, reason) => Err(QueryError::Field(path, reason)),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    // the repeated keys like `tag=a&tag=b` and optionally
+    // comma-separated values like `ids=1,2,3` is read as sequences,
+    // but nested fields is not supported
+    fn get_query_list<T>(&self, split_commas: bool) -> Result<Option<T>, QueryError>
+        where for<'de> T: de::Deserialize<'de>
+    {
+        if let Some(q) = self.get_query_str() {
+            match query_list::from_str(q, split_commas) {
+                Tracked::Value(value) => Ok(Some(value)),
+                Tracked::Error(path, reason) => Err(QueryError::Field(path, reason)),
+            }
         } else {
             Ok(None)
         }
@@ -20,3 +142,242 @@ impl<T> ReadQuery for Request<T> {
         self.uri().query()
     }
 }
+
+impl ReadQuery for Uri {
+    fn get_query_str(&self) -> Option<&str> {
+        self.query()
+    }
+}
+
+fn with_query(uri: &Uri, query: Option<&str>) -> Result<Uri, http::Error> {
+    let mut path_and_query = String::from(uri.path());
+    if let Some(query) = query {
+        path_and_query.push('?');
+        path_and_query.push_str(query);
+    }
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::from_shared(Bytes::from(path_and_query))?);
+    Ok(Uri::from_parts(parts)?)
+}
+
+// the decoded key before the first bracket like `tag` of `tag[0]`
+fn top_key(pair: &str) -> String {
+    let key = pair.split('=').next().unwrap_or("");
+    let key = form_urlencoded::parse(key.as_bytes())
+        .next().map(|(key, _)| key.into_owned()).unwrap_or_default();
+    match key.find('[') {
+        Some(end) => key[..end].into(),
+        None => key,
+    }
+}
+
+pub trait WriteQuery: ReadQuery {
+    fn set_query_str(&mut self, query: Option<&str>) -> Result<(), QueryError>;
+
+    // replaces the whole query
+    fn set_query<T>(&mut self, query: &T) -> Result<(), QueryError>
+        where T: ser::Serialize
+    {
+        let query = qs::to_string(query)?;
+        self.set_query_str(if query.is_empty() { None } else { Some(&query) })
+    }
+
+    // replaces the fields which is set and keeps the others,
+    // the nested fields and sequences is replaced as a whole
+    fn merge_query<T>(&mut self, query: &T) -> Result<(), QueryError>
+        where T: ser::Serialize
+    {
+        let query = qs::to_string(query)?;
+        let new_keys: HashSet<String> = query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(top_key)
+            .collect();
+        let mut merged: Vec<&str> = self.get_query_str()
+            .map(|old| old.split('&')
+                 .filter(|pair| !pair.is_empty())
+                 .filter(|pair| !new_keys.contains(&top_key(pair)))
+                 .collect())
+            .unwrap_or_default();
+        merged.extend(query.split('&').filter(|pair| !pair.is_empty()));
+        let merged = merged.join("&");
+        self.set_query_str(if merged.is_empty() { None } else { Some(&merged) })
+    }
+}
+
+impl<T> WriteQuery for Request<T> {
+    fn set_query_str(&mut self, query: Option<&str>) -> Result<(), QueryError> {
+        *self.uri_mut() = with_query(self.uri(), query)?;
+        Ok(())
+    }
+}
+
+impl WriteQuery for Uri {
+    fn set_query_str(&mut self, query: Option<&str>) -> Result<(), QueryError> {
+        *self = with_query(self, query)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Page {
+        offset: u32,
+        limit: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Filter {
+        name: String,
+        page: Page,
+    }
+
+    #[test]
+    fn test_get_query_with() {
+        let a = Request::builder()
+            .uri("/users?name=Alice&page%5Boffset%5D=10&page%5Blimit%5D=5")
+            .body(())
+            .unwrap();
+
+        assert!(a.get_query_with::<Filter>(&QueryConfig::new()).is_err());
+
+        let q: Filter = a.get_query_with(&QueryConfig::new().with_strict(false)).unwrap().unwrap();
+
+        assert_eq!(q, Filter { name: "Alice".into(), page: Page { offset: 10, limit: 5 } });
+    }
+
+    #[test]
+    fn test_get_query_with_err_path() {
+        let a = Request::builder()
+            .uri("/users?name=Alice&page[offset]=10&page[limit]=many")
+            .body(())
+            .unwrap();
+        let e = a.get_query_with::<Filter>(&QueryConfig::new()).unwrap_err();
+
+        assert_eq!(e.path(), Some("page[limit]"));
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+
+        let a = Request::builder()
+            .uri("/users?name=Alice&page[offset]=10")
+            .body(())
+            .unwrap();
+        let e = a.get_query_with::<Filter>(&QueryConfig::new()).unwrap_err();
+
+        assert_eq!(e.path(), Some("page"));
+        assert!(e.to_string().contains("limit"));
+    }
+
+    #[test]
+    fn test_get_query_with_max_depth() {
+        let a = Request::builder()
+            .uri("/users?name=Alice&page[offset]=10&page[limit]=5")
+            .body(())
+            .unwrap();
+        let e = a.get_query_with::<Filter>(&QueryConfig::new().with_max_depth(0)).unwrap_err();
+
+        // the nested fields is not parsed beyond the max depth
+        assert_eq!(e.path(), Some("page"));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct List {
+        tag: Vec<String>,
+        ids: Option<Vec<u32>>,
+        name: Option<String>,
+        limit: u32,
+    }
+
+    #[test]
+    fn test_get_query_list() {
+        let a = Request::builder()
+            .uri("/items?tag=a&limit=10&tag=b%2Cc&ids=1,2&ids=3&name=x,y")
+            .body(())
+            .unwrap();
+        let q: List = a.get_query_list(true).unwrap().unwrap();
+
+        assert_eq!(q, List { tag: vec!["a".into(), "b".into(), "c".into()],
+                             ids: Some(vec![1, 2, 3]), name: Some("x,y".into()), limit: 10 });
+
+        let a = Request::builder()
+            .uri("/items?tag=a,b&limit=10")
+            .body(())
+            .unwrap();
+        let q: List = a.get_query_list(false).unwrap().unwrap();
+
+        assert_eq!(q, List { tag: vec!["a,b".into()], ids: None, name: None, limit: 10 });
+    }
+
+    #[test]
+    fn test_get_query_list_err() {
+        let a = Request::builder()
+            .uri("/items?tag=a&ids=1,x&limit=10")
+            .body(())
+            .unwrap();
+        let e = a.get_query_list::<List>(true).unwrap_err();
+
+        assert_eq!(e.path(), Some("ids[1]"));
+
+        let a = Request::builder()
+            .uri("/items?tag=a&limit=10&limit=20")
+            .body(())
+            .unwrap();
+        let e = a.get_query_list::<List>(true).unwrap_err();
+
+        assert_eq!(e.path(), Some("limit"));
+    }
+
+    #[test]
+    fn test_set_query() {
+        let mut a = Request::builder()
+            .uri("/users?name=Bob")
+            .body(())
+            .unwrap();
+        a.set_query(&Filter { name: "Alice Smith".into(), page: Page { offset: 10, limit: 5 } }).unwrap();
+
+        assert_eq!(a.uri().path(), "/users");
+
+        let q: Filter = a.get_query().unwrap().unwrap();
+
+        assert_eq!(q, Filter { name: "Alice Smith".into(), page: Page { offset: 10, limit: 5 } });
+    }
+
+    #[test]
+    fn test_set_query_uri() {
+        let mut u: Uri = "https://example.com/path/to?x=1#".parse().unwrap();
+        u.set_query(&Page { offset: 0, limit: 20 }).unwrap();
+
+        assert_eq!(u.to_string(), "https://example.com/path/to?offset=0&limit=20");
+
+        u.set_query_str(None).unwrap();
+
+        assert_eq!(u.to_string(), "https://example.com/path/to");
+    }
+
+    #[test]
+    fn test_merge_query() {
+        let mut u: Uri = "/items?sort=name&offset=30&limit=10".parse().unwrap();
+        u.merge_query(&Page { offset: 40, limit: 10 }).unwrap();
+
+        assert_eq!(u.path(), "/items");
+        assert_eq!(u.query(), Some("sort=name&offset=40&limit=10"));
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Tags {
+        tag: Vec<String>,
+    }
+
+    #[test]
+    fn test_merge_query_seq() {
+        let mut u: Uri = "/items?tag[0]=x&sort=name&tag[1]=y&tag[2]=z".parse().unwrap();
+        u.merge_query(&Tags { tag: vec!["a".into(), "b".into()] }).unwrap();
+
+        let q: Tags = u.get_query().unwrap().unwrap();
+
+        assert_eq!(q.tag, vec!["a", "b"]);
+        assert_eq!(u.query().unwrap().matches("tag").count(), 2);
+        assert!(u.query().unwrap().starts_with("sort=name&"));
+    }
+}
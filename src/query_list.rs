@@ -0,0 +1,253 @@
+use std::collections::{HashMap};
+use serde::forward_to_deserialize_any;
+use serde::de::{self, Deserialize, Deserializer, Visitor, IntoDeserializer, Unexpected};
+use serde::de::value::{Error, MapDeserializer, SeqDeserializer};
+use url::form_urlencoded;
+use super::query_path::{Tracked};
+
+// Deserializes flat query where repeated keys is collected into
+// sequences and comma-separated values optionally split.
+pub(crate) fn from_str<T>(query: &str, split_commas: bool) -> Tracked<T>
+    where for<'de> T: Deserialize<'de>
+{
+    from_entries(group(form_urlencoded::parse(query.as_bytes()).into_owned()), split_commas)
+}
+
+// Deserializes the pairs of names and single values like path params.
+pub(crate) fn from_pairs<T>(pairs: Vec<(String, String)>) -> Tracked<T>
+    where for<'de> T: Deserialize<'de>
+{
+    from_entries(group(pairs), false)
+}
+
+// the values of repeated keys is grouped in order of first occurrence
+fn group<I>(pairs: I) -> Vec<(String, Vec<String>)>
+    where I: IntoIterator<Item = (String, String)>
+{
+    let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (key, value) in pairs {
+        if let Some(&pos) = index.get(&key) {
+            entries[pos].1.push(value);
+            continue;
+        }
+        index.insert(key.clone(), entries.len());
+        entries.push((key, vec![value]));
+    }
+    entries
+}
+
+fn from_entries<T>(entries: Vec<(String, Vec<String>)>, split_commas: bool) -> Tracked<T>
+    where for<'de> T: Deserialize<'de>
+{
+    Tracked::deserialize(QueryMap { entries, split_commas })
+        .unwrap_or_else(|error| Tracked::Error(String::new(), error.to_string()))
+}
+
+struct QueryMap {
+    entries: Vec<(String, Vec<String>)>,
+    split_commas: bool,
+}
+
+impl<'de> Deserializer<'de> for QueryMap {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        let split_commas = self.split_commas;
+        visitor.visit_map(MapDeserializer::new(self.entries.into_iter()
+            .map(|(key, values)| (key, Values { values, split_commas }))))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Values {
+    values: Vec<String>,
+    split_commas: bool,
+}
+
+impl Values {
+    fn single(mut self) -> Result<Value, Error> {
+        if self.values.len() > 1 {
+            return Err(de::Error::custom("multiple values for one key"));
+        }
+        Ok(Value(self.values.pop().unwrap_or_default()))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Values {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! forward_single {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+                where V: Visitor<'de>
+            {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Values {
+    type Error = Error;
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        let values: Vec<Value> = if self.split_commas {
+            self.values.iter()
+                .flat_map(|value| value.split(','))
+                .filter(|value| !value.is_empty())
+                .map(|value| Value(value.into()))
+                .collect()
+        } else {
+            self.values.into_iter().map(Value).collect()
+        };
+        let mut seq = SeqDeserializer::new(values.into_iter());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_some(self)
+    }
+
+    forward_single! {
+        deserialize_any deserialize_bool
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char
+        deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_unit deserialize_identifier deserialize_ignored_any
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, _len: usize, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.single()?.deserialize_map(visitor)
+    }
+
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+}
+
+struct Value(String);
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+                where V: Visitor<'de>
+            {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_string(self.0)
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+        where V: Visitor<'de>
+    {
+        self.0.into_deserializer().deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
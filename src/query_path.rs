@@ -0,0 +1,314 @@
+use std::fmt;
+use std::cell::{RefCell};
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, Visitor, MapAccess, SeqAccess, EnumAccess};
+
+// The wrapper which tracks the path of field while deserializing,
+// when deserialization fails the path of the deepest failed field
+// is kept together with the error message.
+pub(crate) enum Tracked<T> {
+    Value(T),
+    Error(String, String),
+}
+
+impl<'de, T> Deserialize<'de> for Tracked<T>
+    where T: Deserialize<'de>
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where D: Deserializer<'de>
+    {
+        let track = Track::default();
+        let result = T::deserialize(Wrap { de: deserializer, path: String::new(), track: &track, key: false });
+        Ok(match result {
+            Ok(value) => Tracked::Value(value),
+            Err(error) => Tracked::Error(track.path.into_inner().unwrap_or_default(), error.to_string()),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Track {
+    path: RefCell<Option<String>>,
+    key: RefCell<Option<String>>,
+}
+
+impl Track {
+    fn fail(&self, path: &str) {
+        let mut failed = self.path.borrow_mut();
+        if failed.is_none() {
+            *failed = Some(path.into());
+        }
+    }
+
+    fn child(&self, path: &str, key: &str) -> String {
+        if path.is_empty() {
+            key.into()
+        } else {
+            format!("{}[{}]", path, key)
+        }
+    }
+}
+
+struct Wrap<'t, D> {
+    de: D,
+    path: String,
+    track: &'t Track,
+    // the key of map is captured to build the path of value
+    key: bool,
+}
+
+impl<'t, D> Wrap<'t, D> {
+    fn visitor<V>(&self, visitor: V) -> WrapVisitor<'t, V> {
+        WrapVisitor { visitor, path: self.path.clone(), track: self.track, key: self.key }
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $type:ty),*);)*) => {
+        $(
+            fn $method<V>(self, $($arg: $type,)* visitor: V) -> Result<V::Value, D::Error>
+                where V: Visitor<'de>
+            {
+                let visitor = self.visitor(visitor);
+                let (track, path) = (self.track, self.path);
+                self.de.$method($($arg,)* visitor).map_err(|error| {
+                    track.fail(&path);
+                    error
+                })
+            }
+        )*
+    };
+}
+
+impl<'t, 'de, D> Deserializer<'de> for Wrap<'t, D>
+    where D: Deserializer<'de>
+{
+    type Error = D::Error;
+
+    forward_deserialize! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+}
+
+struct WrapVisitor<'t, V> {
+    visitor: V,
+    path: String,
+    track: &'t Track,
+    key: bool,
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($type:ty);)*) => {
+        $(
+            fn $method<E>(self, value: $type) -> Result<V::Value, E>
+                where E: de::Error
+            {
+                if self.key {
+                    *self.track.key.borrow_mut() = Some(value.to_string());
+                }
+                self.visitor.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'t, 'de, V> Visitor<'de> for WrapVisitor<'t, V>
+    where V: Visitor<'de>
+{
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.visitor.expecting(f)
+    }
+
+    forward_visit! {
+        visit_bool(bool);
+        visit_i8(i8);
+        visit_i16(i16);
+        visit_i32(i32);
+        visit_i64(i64);
+        visit_u8(u8);
+        visit_u16(u16);
+        visit_u32(u32);
+        visit_u64(u64);
+        visit_f32(f32);
+        visit_f64(f64);
+        visit_char(char);
+        visit_str(&str);
+        visit_borrowed_str(&'de str);
+        visit_string(String);
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<V::Value, E>
+        where E: de::Error
+    {
+        if self.key {
+            *self.track.key.borrow_mut() = Some(String::from_utf8_lossy(value).into_owned());
+        }
+        self.visitor.visit_bytes(value)
+    }
+
+    fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> Result<V::Value, E>
+        where E: de::Error
+    {
+        if self.key {
+            *self.track.key.borrow_mut() = Some(String::from_utf8_lossy(value).into_owned());
+        }
+        self.visitor.visit_borrowed_bytes(value)
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<V::Value, E>
+        where E: de::Error
+    {
+        if self.key {
+            *self.track.key.borrow_mut() = Some(String::from_utf8_lossy(&value).into_owned());
+        }
+        self.visitor.visit_byte_buf(value)
+    }
+
+    fn visit_none<E>(self) -> Result<V::Value, E>
+        where E: de::Error
+    {
+        self.visitor.visit_none()
+    }
+
+    fn visit_unit<E>(self) -> Result<V::Value, E>
+        where E: de::Error
+    {
+        self.visitor.visit_unit()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+        where D: Deserializer<'de>
+    {
+        self.visitor.visit_some(Wrap { de: deserializer, path: self.path, track: self.track, key: self.key })
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+        where D: Deserializer<'de>
+    {
+        self.visitor.visit_newtype_struct(Wrap { de: deserializer, path: self.path, track: self.track, key: self.key })
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<V::Value, A::Error>
+        where A: SeqAccess<'de>
+    {
+        self.visitor.visit_seq(WrapSeq { seq, path: self.path, track: self.track, index: 0 })
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<V::Value, A::Error>
+        where A: MapAccess<'de>
+    {
+        self.visitor.visit_map(WrapMap { map, path: self.path, track: self.track })
+    }
+
+    // the variants is not tracked
+    fn visit_enum<A>(self, data: A) -> Result<V::Value, A::Error>
+        where A: EnumAccess<'de>
+    {
+        self.visitor.visit_enum(data)
+    }
+}
+
+struct WrapSeed<'t, S> {
+    seed: S,
+    path: String,
+    track: &'t Track,
+    key: bool,
+}
+
+impl<'t, 'de, S> DeserializeSeed<'de> for WrapSeed<'t, S>
+    where S: DeserializeSeed<'de>
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<S::Value, D::Error>
+        where D: Deserializer<'de>
+    {
+        self.seed.deserialize(Wrap { de: deserializer, path: self.path, track: self.track, key: self.key })
+    }
+}
+
+struct WrapSeq<'t, A> {
+    seq: A,
+    path: String,
+    track: &'t Track,
+    index: usize,
+}
+
+impl<'t, 'de, A> SeqAccess<'de> for WrapSeq<'t, A>
+    where A: SeqAccess<'de>
+{
+    type Error = A::Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, A::Error>
+        where S: DeserializeSeed<'de>
+    {
+        let path = self.track.child(&self.path, &self.index.to_string());
+        self.index += 1;
+        self.seq.next_element_seed(WrapSeed { seed, path, track: self.track, key: false })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.seq.size_hint()
+    }
+}
+
+struct WrapMap<'t, A> {
+    map: A,
+    path: String,
+    track: &'t Track,
+}
+
+impl<'t, 'de, A> MapAccess<'de> for WrapMap<'t, A>
+    where A: MapAccess<'de>
+{
+    type Error = A::Error;
+
+    fn next_key_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, A::Error>
+        where S: DeserializeSeed<'de>
+    {
+        *self.track.key.borrow_mut() = None;
+        self.map.next_key_seed(WrapSeed { seed, path: self.path.clone(), track: self.track, key: true })
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, A::Error>
+        where S: DeserializeSeed<'de>
+    {
+        let key = self.track.key.borrow_mut().take().unwrap_or_else(|| "?".into());
+        let path = self.track.child(&self.path, &key);
+        self.map.next_value_seed(WrapSeed { seed, path, track: self.track, key: false })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.map.size_hint()
+    }
+}
+
@@ -1,30 +1,513 @@
-use http::{Request};
+use std::{fmt, error};
+use std::borrow::{Cow};
+use std::sync::{Arc};
+use http::{Request, StatusCode};
+use serde::{ser, de};
+use serde_qs as qs;
+use url::form_urlencoded;
+use url::percent_encoding::{percent_decode, utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use super::query_path::{Tracked};
+use super::query_list;
+
+#[derive(Debug)]
+pub enum RouteError {
+    // the path does not match the pattern
+    Mismatch,
+    // the name of param and the reason
+    Param(String, String),
+    // the raw segment which is not allowed
+    Segment(String),
+    // the name of route which is not registered
+    Name(String),
+}
+
+impl RouteError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RouteError::Mismatch => StatusCode::NOT_FOUND,
+            RouteError::Param(..) | RouteError::Segment(..) => StatusCode::BAD_REQUEST,
+            RouteError::Name(..) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RouteError::Mismatch => f.write_str("Path does not match"),
+            RouteError::Param(name, reason) if name.is_empty() => write!(f, "Invalid path: {}", reason),
+            RouteError::Param(name, reason) => write!(f, "Invalid path param {}: {}", name, reason),
+            RouteError::Segment(segment) => write!(f, "Invalid path segment `{}`", segment),
+            RouteError::Name(name) => write!(f, "Unknown route {}", name),
+        }
+    }
+}
+
+impl error::Error for RouteError {}
+
+// the names and decoded values of params
+pub type PathParams = Vec<(String, String)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+// The pattern like `/users/{id}/posts/{post}` which matches
+// the leading segments of path and captures the params.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern {
+    segments: Vec<Segment>,
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> Self {
+        let segments = pattern.split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| if segment.len() > 2 && segment.starts_with('{') && segment.ends_with('}') {
+                Segment::Param(segment[1..segment.len() - 1].into())
+            } else {
+                Segment::Literal(segment.into())
+            })
+            .collect();
+        PathPattern { segments }
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Param(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    // returns the percent-decoded params and the rest of path
+    pub fn match_path<'a>(&self, path: &'a str) -> Result<(PathParams, &'a str), RouteError> {
+        let mut params = Vec::new();
+        let mut rest = path;
+        for segment in &self.segments {
+            if !rest.starts_with('/') {
+                return Err(RouteError::Mismatch);
+            }
+            let end = rest[1..].find('/').map(|end| end + 1).unwrap_or(rest.len());
+            let value = &rest[1..end];
+            match segment {
+                Segment::Literal(literal) => if value != literal {
+                    return Err(RouteError::Mismatch);
+                },
+                Segment::Param(name) => {
+                    if value.is_empty() {
+                        return Err(RouteError::Mismatch);
+                    }
+                    let value = percent_decode(value.as_bytes()).decode_utf8()
+                        .map_err(|error| RouteError::Param(name.clone(), error.to_string()))?;
+                    params.push((name.clone(), value.into_owned()));
+                },
+            }
+            rest = &rest[end..];
+        }
+        Ok((params, rest))
+    }
+
+    pub fn join(&self, pattern: &PathPattern) -> PathPattern {
+        let segments = self.segments.iter().chain(&pattern.segments).cloned().collect();
+        PathPattern { segments }
+    }
+
+    // the used params is taken out from the list
+    pub fn build(&self, params: &mut PathParams) -> Result<String, RouteError> {
+        let mut path = String::new();
+        for segment in &self.segments {
+            path.push('/');
+            match segment {
+                Segment::Literal(literal) => path.push_str(literal),
+                Segment::Param(name) => {
+                    let index = params.iter().position(|(key, _)| key == name)
+                        .ok_or_else(|| RouteError::Param(name.clone(), "missing value".into()))?;
+                    let (_, value) = params.remove(index);
+                    if value.is_empty() {
+                        return Err(RouteError::Param(name.clone(), "empty value".into()));
+                    }
+                    path.extend(utf8_percent_encode(&value, PATH_SEGMENT_ENCODE_SET));
+                },
+            }
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        Ok(path)
+    }
+}
+
+impl<'a> From<&'a str> for PathPattern {
+    fn from(pattern: &'a str) -> Self {
+        PathPattern::new(pattern)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentPolicy {
+    // the empty, `.` and `..` segments is an error,
+    // as well as the decoded separators and nul
+    Strict,
+    // the empty and `.` segments is skipped, others like in strict
+    Lenient,
+    // all segments is kept as is
+    Raw,
+}
+
+// The iterator over percent-decoded segments of path,
+// the trailing slash does not give the empty segment.
+pub struct Segments<'a> {
+    rest: &'a str,
+    policy: SegmentPolicy,
+}
+
+impl<'a> Segments<'a> {
+    pub fn new(path: &'a str, policy: SegmentPolicy) -> Self {
+        Segments { rest: path, policy }
+    }
+
+    // the path after the segments which is iterated
+    pub fn rest(&self) -> &'a str {
+        self.rest
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Result<Cow<'a, str>, RouteError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.rest.is_empty() || self.rest == "/" {
+                return None;
+            }
+            let start = if self.rest.starts_with('/') { 1 } else { 0 };
+            let end = self.rest[start..].find('/').map(|end| end + start).unwrap_or(self.rest.len());
+            let raw = &self.rest[start..end];
+            self.rest = &self.rest[end..];
+
+            let segment = match percent_decode(raw.as_bytes()).decode_utf8() {
+                Ok(segment) => segment,
+                Err(_) => return Some(Err(RouteError::Segment(raw.into()))),
+            };
+            match (self.policy, segment.as_ref()) {
+                (SegmentPolicy::Raw, _) => (),
+                (SegmentPolicy::Lenient, "") | (SegmentPolicy::Lenient, ".") => continue,
+                (_, "") | (_, ".") | (_, "..") => return Some(Err(RouteError::Segment(raw.into()))),
+                (_, segment) if segment.contains(&['/', '\\', '\0'][..]) =>
+                    return Some(Err(RouteError::Segment(raw.into()))),
+                _ => (),
+            }
+            return Some(Ok(segment));
+        }
+    }
+}
+
+// The patterns of named routes to build the paths of.
+#[derive(Debug, Clone, Default)]
+pub struct RouteNames {
+    routes: Vec<(String, PathPattern)>,
+}
+
+impl RouteNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<S, P>(&mut self, name: S, pattern: P)
+        where S: Into<String>,
+              P: Into<PathPattern>
+    {
+        self.routes.push((name.into(), pattern.into()));
+    }
+
+    // the routes of other is placed under the prefix
+    pub fn extend_with_prefix(&mut self, prefix: &PathPattern, other: &RouteNames) {
+        self.routes.extend(other.routes.iter()
+                           .map(|(name, pattern)| (name.clone(), prefix.join(pattern))));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PathPattern> {
+        self.routes.iter()
+            .find(|(route, _)| route == name)
+            .map(|(_, pattern)| pattern)
+    }
+
+    // the params which is not used in path is added to query
+    pub fn url_for<T>(&self, name: &str, params: &T) -> Result<String, RouteError>
+        where T: ser::Serialize
+    {
+        let pattern = self.get(name).ok_or_else(|| RouteError::Name(name.into()))?;
+        let query = qs::to_string(params)
+            .map_err(|error| RouteError::Param(String::new(), error.to_string()))?;
+        let mut params: PathParams = form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+        let mut url = pattern.build(&mut params)?;
+        if !params.is_empty() {
+            url.push('?');
+            url.push_str(&form_urlencoded::Serializer::new(String::new())
+                         .extend_pairs(params)
+                         .finish());
+        }
+        Ok(url)
+    }
+}
 
 pub struct RoutedRequest<B> {
     request: Request<B>,
     split: usize, // pre-routed path split
+    params: PathParams, // params captured while routing
+    names: Option<(Arc<RouteNames>, usize)>, // named routes and the split of root
 }
 
 impl<B> RoutedRequest<B> {
     pub fn inner(self) -> Request<B> {
         self.request
     }
-    
+
+    pub fn request(&self) -> &Request<B> {
+        &self.request
+    }
+
     pub fn route(self, path: &str) -> Self {
         Self { split: self.request.uri().path().len() - path.len(), ..self }
     }
 
+    // the split is advanced only when the path matches
+    pub fn route_params<T>(&mut self, pattern: &PathPattern) -> Result<T, RouteError>
+        where for<'de> T: de::Deserialize<'de>
+    {
+        let (params, rest) = pattern.match_path(self.path())?;
+        let rest = rest.len();
+        let value = from_params(params.clone())?;
+        self.advance(params, rest);
+        Ok(value)
+    }
+
+    // all params captured so far including the matched by router
+    pub fn params<T>(&self) -> Result<T, RouteError>
+        where for<'de> T: de::Deserialize<'de>
+    {
+        from_params(self.params.clone())
+    }
+
+    // builds the path of named route under the prefix where the root router is mounted
+    pub fn url_for<T>(&self, name: &str, params: &T) -> Result<String, RouteError>
+        where T: ser::Serialize
+    {
+        let (names, base) = self.names.as_ref().ok_or_else(|| RouteError::Name(name.into()))?;
+        let url = names.url_for(name, params)?;
+        Ok(format!("{}{}", self.request.uri().path().split_at(*base).0, url))
+    }
+
+    // the names is kept from the first router
+    pub(crate) fn set_names(&mut self, names: &Arc<RouteNames>) {
+        if self.names.is_none() {
+            self.names = Some((names.clone(), self.split));
+        }
+    }
+
+    pub(crate) fn advance(&mut self, params: PathParams, rest: usize) {
+        self.split = self.request.uri().path().len() - rest;
+        self.params.extend(params);
+    }
+
     pub fn prefix(&self) -> &str {
         self.request.uri().path().split_at(self.split).0
     }
-    
+
     pub fn path(&self) -> &str {
         self.request.uri().path().split_at(self.split).1
     }
+
+    pub fn segments(&self, policy: SegmentPolicy) -> Segments<'_> {
+        Segments::new(self.path(), policy)
+    }
+
+    // the split is advanced only when enough segments is available
+    pub fn route_segments(&mut self, count: usize, policy: SegmentPolicy) -> Result<Vec<String>, RouteError> {
+        let (segments, rest) = {
+            let mut iter = self.segments(policy);
+            let segments = iter.by_ref().take(count)
+                .map(|segment| segment.map(Cow::into_owned))
+                .collect::<Result<Vec<_>, _>>()?;
+            (segments, iter.rest().len())
+        };
+        if segments.len() < count {
+            return Err(RouteError::Mismatch);
+        }
+        self.advance(Vec::new(), rest);
+        Ok(segments)
+    }
 }
 
 impl<B> From<Request<B>> for RoutedRequest<B> {
     fn from(request: Request<B>) -> Self {
-        Self { request, split: 0 }
+        Self { request, split: 0, params: Vec::new(), names: None }
+    }
+}
+
+fn from_params<T>(params: PathParams) -> Result<T, RouteError>
+    where for<'de> T: de::Deserialize<'de>
+{
+    match query_list::from_pairs(params) {
+        Tracked::Value(value) => Ok(value),
+        Tracked::Error(name, reason) => Err(RouteError::Param(name, reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Post {
+        id: u32,
+        post: String,
+    }
+
+    fn request(uri: &str) -> RoutedRequest<()> {
+        Request::builder().uri(uri).body(()).unwrap().into()
+    }
+
+    #[test]
+    fn test_route_params() {
+        let mut r = request("/api/users/12/posts/hello%20world/comments");
+        r = r.route("/users/12/posts/hello%20world/comments");
+        let p: Post = r.route_params(&"/users/{id}/posts/{post}".into()).unwrap();
+
+        assert_eq!(p, Post { id: 12, post: "hello world".into() });
+        assert_eq!(r.prefix(), "/api/users/12/posts/hello%20world");
+        assert_eq!(r.path(), "/comments");
+
+        let m: HashMap<String, String> = r.route_params(&"/comments".into()).unwrap();
+
+        assert!(m.is_empty());
+        assert_eq!(r.path(), "");
+
+        let p: Post = r.params().unwrap();
+
+        assert_eq!(p, Post { id: 12, post: "hello world".into() });
+    }
+
+    #[test]
+    fn test_route_params_mismatch() {
+        let mut r = request("/users/12/postsx/1");
+
+        let e = r.route_params::<Post>(&"/users/{id}/posts/{post}".into()).unwrap_err();
+        assert_eq!(e.status(), StatusCode::NOT_FOUND);
+
+        let e = r.route_params::<Post>(&"/users/{id}/postsx/{post}/more".into()).unwrap_err();
+        assert_eq!(e.status(), StatusCode::NOT_FOUND);
+
+        let e = r.route_params::<Post>(&"/users/{id}/post".into()).unwrap_err();
+        assert_eq!(e.status(), StatusCode::NOT_FOUND);
+
+        assert_eq!(r.path(), "/users/12/postsx/1");
+    }
+
+    #[test]
+    fn test_route_params_err_param() {
+        let mut r = request("/users/alice/posts/1");
+        let e = r.route_params::<Post>(&"/users/{id}/posts/{post}".into()).unwrap_err();
+
+        match &e {
+            RouteError::Param(name, _) => assert_eq!(name, "id"),
+            _ => panic!("unexpected error {}", e),
+        }
+        assert_eq!(e.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(r.path(), "/users/alice/posts/1");
+    }
+
+    #[test]
+    fn test_pattern_params() {
+        let p = PathPattern::new("/users/{id}/posts/{post}/");
+
+        assert_eq!(p.params().collect::<Vec<_>>(), vec!["id", "post"]);
+        assert_eq!(p.match_path("/users/1/posts/2/").unwrap().1, "/");
+    }
+
+    #[test]
+    fn test_segments() {
+        let r = request("/files/a%20b/./c/");
+        let s = r.segments(SegmentPolicy::Strict).collect::<Vec<_>>();
+
+        assert_eq!(s.len(), 4);
+        assert_eq!(s[0].as_ref().unwrap(), "files");
+        assert_eq!(s[1].as_ref().unwrap(), "a b");
+        assert!(s[2].is_err());
+
+        let s = r.segments(SegmentPolicy::Lenient).collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(s, vec!["files", "a b", "c"]);
+
+        for raw in &["%2Fetc", "..%5Cetc", "a%00b"] {
+            let r = request(&format!("/files/{}", raw));
+
+            for policy in &[SegmentPolicy::Strict, SegmentPolicy::Lenient] {
+                match r.segments(*policy).nth(1) {
+                    Some(Err(RouteError::Segment(segment))) => assert_eq!(&segment, raw),
+                    _ => panic!("expected error for {}", raw),
+                }
+            }
+            assert!(r.segments(SegmentPolicy::Raw).all(|segment| segment.is_ok()));
+        }
+
+        let r = request("/a//%2E%2E/b");
+        let s = r.segments(SegmentPolicy::Lenient).collect::<Vec<_>>();
+
+        match &s[1] {
+            Err(RouteError::Segment(raw)) => assert_eq!(raw, "%2E%2E"),
+            _ => panic!("expected error"),
+        }
+
+        let s = r.segments(SegmentPolicy::Raw).collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(s, vec!["a", "", "..", "b"]);
+    }
+
+    #[test]
+    fn test_route_segments() {
+        let mut r = request("/static/css//site.css");
+
+        assert_eq!(r.route_segments(1, SegmentPolicy::Strict).unwrap(), vec!["static"]);
+        assert_eq!(r.prefix(), "/static");
+        assert_eq!(r.path(), "/css//site.css");
+
+        assert_eq!(r.route_segments(3, SegmentPolicy::Lenient).unwrap_err().status(), StatusCode::NOT_FOUND);
+        assert_eq!(r.route_segments(2, SegmentPolicy::Strict).unwrap_err().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(r.path(), "/css//site.css");
+
+        assert_eq!(r.route_segments(2, SegmentPolicy::Lenient).unwrap(), vec!["css", "site.css"]);
+        assert_eq!(r.path(), "");
+    }
+
+    #[derive(Serialize)]
+    struct PostRef<'a> {
+        id: u32,
+        post: &'a str,
+        highlight: Option<&'a str>,
+    }
+
+    #[test]
+    fn test_url_for() {
+        let mut n = RouteNames::new();
+        n.add("post", "/users/{id}/posts/{post}");
+        let mut m = RouteNames::new();
+        m.extend_with_prefix(&"/api".into(), &n);
+
+        assert_eq!(m.url_for("post", &PostRef { id: 1, post: "a b/c", highlight: None }).unwrap(),
+                   "/api/users/1/posts/a%20b%2Fc");
+        assert_eq!(m.url_for("post", &PostRef { id: 1, post: "x", highlight: Some("a&b") }).unwrap(),
+                   "/api/users/1/posts/x?highlight=a%26b");
+        assert!(m.url_for("user", &PostRef { id: 1, post: "x", highlight: None }).is_err());
+
+        let mut r = request("/app/api/users/1/posts/x");
+        r = r.route("/api/users/1/posts/x");
+        r.set_names(&Arc::new(m));
+
+        assert_eq!(r.url_for("post", &PostRef { id: 2, post: "y", highlight: None }).unwrap(),
+                   "/app/api/users/2/posts/y");
     }
 }
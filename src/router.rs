@@ -0,0 +1,372 @@
+use std::error::Error;
+use std::sync::{Arc};
+use futures::{Future, IntoFuture, future};
+use http::{Method, Response, StatusCode};
+use http::header::{HeaderValue, ALLOW};
+use hyper::{Body};
+use super::{RoutedRequest, RouteNames, RouteError, PathPattern, PathParams,
+            QueryError, CodecError, BodyError, MultipartError};
+
+pub type HandlerError = Box<dyn Error + Send + Sync>;
+pub type HandlerFuture = Box<dyn Future<Item = Response<Body>, Error = HandlerError> + Send>;
+
+type Handler = Box<dyn Fn(RoutedRequest<Body>) -> HandlerFuture + Send + Sync>;
+
+struct Route {
+    method: Method,
+    pattern: PathPattern,
+    handler: Handler,
+}
+
+enum Target {
+    Router(Router),
+    Handler(Handler),
+}
+
+struct Mount {
+    pattern: PathPattern,
+    target: Target,
+}
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    mounts: Vec<Mount>,
+    names: Arc<RouteNames>,
+}
+
+pub(crate) fn empty_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = status;
+    response
+}
+
+// the status of errors which is caused by request,
+// others is passed to server as is
+fn error_status(error: &HandlerError) -> Option<StatusCode> {
+    if let Some(error) = error.downcast_ref::<RouteError>() {
+        return Some(error.status());
+    }
+    if let Some(error) = error.downcast_ref::<QueryError>() {
+        return Some(error.status());
+    }
+    if let Some(error) = error.downcast_ref::<CodecError>() {
+        return Some(error.status());
+    }
+    match error.downcast_ref::<BodyError>() {
+        Some(BodyError::TooLarge) => return Some(StatusCode::PAYLOAD_TOO_LARGE),
+        Some(BodyError::Transport(_)) => return None,
+        None => (),
+    }
+    match error.downcast_ref::<MultipartError>() {
+        Some(MultipartError::TooLarge) => Some(StatusCode::PAYLOAD_TOO_LARGE),
+        Some(MultipartError::Malformed(_)) | Some(MultipartError::InvalidValue(_)) => Some(StatusCode::BAD_REQUEST),
+        _ => None,
+    }
+}
+
+fn boxed_handler<F, R>(handler: F) -> Handler
+    where F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+          R: IntoFuture<Item = Response<Body>>,
+          R::Future: Send + 'static,
+          R::Error: Into<HandlerError> + 'static,
+{
+    Box::new(move |request| -> HandlerFuture {
+        Box::new(handler(request).into_future().map_err(Into::into)
+                 .or_else(|error| match error_status(&error) {
+                     Some(status) => Ok(empty_response(status)),
+                     None => Err(error),
+                 }))
+    })
+}
+
+// the rest of path may be only the trailing slash
+fn is_full_match(rest: &str) -> bool {
+    rest.is_empty() || rest == "/"
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the handler is called when the whole rest of path matches the pattern
+    pub fn route<P, F, R>(mut self, method: Method, pattern: P, handler: F) -> Self
+        where P: Into<PathPattern>,
+              F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = Response<Body>>,
+              R::Future: Send + 'static,
+              R::Error: Into<HandlerError> + 'static,
+    {
+        self.routes.push(Route { method, pattern: pattern.into(), handler: boxed_handler(handler) });
+        self
+    }
+
+    // names the last added route
+    pub fn name<S>(mut self, name: S) -> Self
+        where S: Into<String>
+    {
+        if let Some(route) = self.routes.last() {
+            Arc::make_mut(&mut self.names).add(name, route.pattern.clone());
+        }
+        self
+    }
+
+    pub fn names(&self) -> &RouteNames {
+        &self.names
+    }
+
+    pub fn get<P, F, R>(self, pattern: P, handler: F) -> Self
+        where P: Into<PathPattern>,
+              F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = Response<Body>>,
+              R::Future: Send + 'static,
+              R::Error: Into<HandlerError> + 'static,
+    {
+        self.route(Method::GET, pattern, handler)
+    }
+
+    pub fn post<P, F, R>(self, pattern: P, handler: F) -> Self
+        where P: Into<PathPattern>,
+              F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = Response<Body>>,
+              R::Future: Send + 'static,
+              R::Error: Into<HandlerError> + 'static,
+    {
+        self.route(Method::POST, pattern, handler)
+    }
+
+    pub fn put<P, F, R>(self, pattern: P, handler: F) -> Self
+        where P: Into<PathPattern>,
+              F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = Response<Body>>,
+              R::Future: Send + 'static,
+              R::Error: Into<HandlerError> + 'static,
+    {
+        self.route(Method::PUT, pattern, handler)
+    }
+
+    pub fn delete<P, F, R>(self, pattern: P, handler: F) -> Self
+        where P: Into<PathPattern>,
+              F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = Response<Body>>,
+              R::Future: Send + 'static,
+              R::Error: Into<HandlerError> + 'static,
+    {
+        self.route(Method::DELETE, pattern, handler)
+    }
+
+    // the sub-router gets the rest of path after the prefix
+    pub fn mount<P>(mut self, pattern: P, router: Router) -> Self
+        where P: Into<PathPattern>
+    {
+        let pattern = pattern.into();
+        Arc::make_mut(&mut self.names).extend_with_prefix(&pattern, &router.names);
+        self.mounts.push(Mount { pattern, target: Target::Router(router) });
+        self
+    }
+
+    // the handler gets the rest of path after the prefix with any method
+    pub fn mount_handler<P, F, R>(mut self, pattern: P, handler: F) -> Self
+        where P: Into<PathPattern>,
+              F: Fn(RoutedRequest<Body>) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = Response<Body>>,
+              R::Future: Send + 'static,
+              R::Error: Into<HandlerError> + 'static,
+    {
+        self.mounts.push(Mount { pattern: pattern.into(), target: Target::Handler(boxed_handler(handler)) });
+        self
+    }
+
+    pub fn handle(&self, mut request: RoutedRequest<Body>) -> HandlerFuture {
+        request.set_names(&self.names);
+        let method = request.request().method().clone();
+        let mut allow: Vec<&Method> = Vec::new();
+        let mut found: Option<(u8, &Route, PathParams, usize)> = None;
+
+        for route in &self.routes {
+            if let Ok((params, rest)) = route.pattern.match_path(request.path()) {
+                if !is_full_match(rest) {
+                    continue;
+                }
+                if !allow.contains(&&route.method) {
+                    allow.push(&route.method);
+                }
+                // the HEAD is served by GET handler unless defined separately
+                let rank = if route.method == method {
+                    2
+                } else if route.method == Method::GET && method == Method::HEAD {
+                    1
+                } else {
+                    continue;
+                };
+                if found.as_ref().map(|(found_rank, ..)| rank > *found_rank).unwrap_or(true) {
+                    found = Some((rank, route, params, rest.len()));
+                }
+            }
+        }
+
+        if let Some((_, route, params, rest)) = found {
+            request.advance(params, rest);
+            return (route.handler)(request);
+        }
+
+        if !allow.is_empty() {
+            if allow.contains(&&Method::GET) && !allow.contains(&&Method::HEAD) {
+                allow.push(&Method::HEAD);
+            }
+            let allow = allow.iter().map(|method| method.as_str()).collect::<Vec<_>>().join(", ");
+            let mut response = empty_response(StatusCode::METHOD_NOT_ALLOWED);
+            if let Ok(allow) = HeaderValue::from_str(&allow) {
+                response.headers_mut().insert(ALLOW, allow);
+            }
+            return Box::new(future::ok(response));
+        }
+
+        for mount in &self.mounts {
+            if let Ok((params, rest)) = mount.pattern.match_path(request.path()) {
+                let rest = rest.len();
+                request.advance(params, rest);
+                return match &mount.target {
+                    Target::Router(router) => router.handle(request),
+                    Target::Handler(handler) => handler(request),
+                };
+            }
+        }
+
+        Box::new(future::ok(empty_response(StatusCode::NOT_FOUND)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{Stream};
+    use http::{Request};
+    use http::header::{LOCATION};
+    use super::super::{ConcatBody, DecodeJson};
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct User {
+        id: u32,
+    }
+
+    fn router() -> Router {
+        let users = Router::new()
+            .get("/", |_| Ok::<_, HandlerError>(Response::new(Body::from("list"))))
+            .post("/", |_| Ok::<_, HandlerError>(empty_response(StatusCode::CREATED)))
+            .get("/{id}", |request: RoutedRequest<Body>| {
+                let user: User = request.params()?;
+                Ok::<_, HandlerError>(Response::new(Body::from(format!("user {} at {}", user.id, request.prefix()))))
+            })
+            .name("user")
+            .get("/{id}/next", |request: RoutedRequest<Body>| {
+                let user: User = request.params()?;
+                let mut response = empty_response(StatusCode::SEE_OTHER);
+                let location = request.url_for("user", &User { id: user.id + 1 })?;
+                response.headers_mut().insert(LOCATION, HeaderValue::from_str(&location)?);
+                Ok::<_, HandlerError>(response)
+            })
+            .delete("/{id}", |_| Ok::<_, HandlerError>(empty_response(StatusCode::NO_CONTENT)))
+            .put("/", |request: RoutedRequest<Body>| {
+                request.inner().concat_body_with_limit(32).map_err(HandlerError::from)
+                    .and_then(|request| {
+                        let request: Request<User> = request.decode_json()?;
+                        Ok(Response::new(Body::from(format!("user {}", request.body().id))))
+                    })
+            });
+
+        Router::new()
+            .get("/", |_| Ok::<_, HandlerError>(Response::new(Body::from("index"))))
+            .mount("/api/users", users)
+            .mount_handler("/files", |request: RoutedRequest<Body>| {
+                Ok::<_, HandlerError>(Response::new(Body::from(format!("{} {}", request.request().method(), request.path()))))
+            })
+    }
+
+    fn handle(router: &Router, method: Method, uri: &str) -> Response<Body> {
+        let request = Request::builder().method(method).uri(uri).body(Body::empty()).unwrap();
+        router.handle(request.into()).wait().unwrap()
+    }
+
+    fn body(response: Response<Body>) -> String {
+        let body = response.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_router_dispatch() {
+        let r = router();
+
+        assert_eq!(body(handle(&r, Method::GET, "/")), "index");
+        assert_eq!(body(handle(&r, Method::GET, "/api/users")), "list");
+        assert_eq!(body(handle(&r, Method::GET, "/api/users/")), "list");
+        assert_eq!(body(handle(&r, Method::GET, "/api/users/7")), "user 7 at /api/users/7");
+        assert_eq!(body(handle(&r, Method::HEAD, "/api/users/7")), "user 7 at /api/users/7");
+        assert_eq!(handle(&r, Method::POST, "/api/users").status(), StatusCode::CREATED);
+        assert_eq!(handle(&r, Method::DELETE, "/api/users/7").status(), StatusCode::NO_CONTENT);
+        assert_eq!(body(handle(&r, Method::PUT, "/files/a/b.txt")), "PUT /a/b.txt");
+    }
+
+    #[test]
+    fn test_router_not_found() {
+        let r = router();
+
+        assert_eq!(handle(&r, Method::GET, "/index").status(), StatusCode::NOT_FOUND);
+        assert_eq!(handle(&r, Method::GET, "/api/users/7/posts").status(), StatusCode::NOT_FOUND);
+        assert_eq!(handle(&r, Method::GET, "/api").status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_router_method_not_allowed() {
+        let r = router();
+        let a = handle(&r, Method::PUT, "/api/users/7");
+
+        assert_eq!(a.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(a.headers()[ALLOW], "GET, DELETE, HEAD");
+
+        let a = handle(&r, Method::DELETE, "/");
+
+        assert_eq!(a.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(a.headers()[ALLOW], "GET, HEAD");
+    }
+
+    #[test]
+    fn test_router_handler_error() {
+        let r = router();
+
+        assert_eq!(handle(&r, Method::GET, "/api/users/bob").status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn put(router: &Router, body: &'static str) -> Response<Body> {
+        let request = Request::builder().method(Method::PUT).uri("/api/users").body(Body::from(body)).unwrap();
+        router.handle(request.into()).wait().unwrap()
+    }
+
+    #[test]
+    fn test_router_body() {
+        let r = router();
+
+        assert_eq!(body(put(&r, r#"{"id":5}"#)), "user 5");
+        assert_eq!(put(&r, r#"{"id":"#).status(), StatusCode::BAD_REQUEST);
+        assert_eq!(put(&r, r#"{"id":5,"name":"a very long user name"}"#).status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_router_url_for() {
+        let r = Router::new().mount("/app", router());
+
+        assert_eq!(r.names().url_for("user", &User { id: 3 }).unwrap(), "/app/api/users/3");
+
+        let a = handle(&r, Method::GET, "/app/api/users/7/next");
+
+        assert_eq!(a.status(), StatusCode::SEE_OTHER);
+        assert_eq!(a.headers()[LOCATION], "/app/api/users/8");
+
+        let request = Request::builder().uri("/site/api/users/7/next").body(Body::empty()).unwrap();
+        let request = RoutedRequest::from(request).route("/api/users/7/next");
+        let a = router().handle(request).wait().unwrap();
+
+        assert_eq!(a.headers()[LOCATION], "/site/api/users/8");
+    }
+}
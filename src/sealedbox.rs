@@ -1,144 +1,123 @@
-use http::{Request, Response};
-use http::header::{HeaderValue};
 use bytes::{Bytes};
 use sodiumoxide::crypto::sealedbox;
-use super::{ReadHeader, WriteHeader, CodecError, CodecResult, UnwrapType, WrapType, PublicKey, SecretKey};
+use super::{WriteHeader, AsTypePattern, CodecError, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody, PublicKey, SecretKey};
 
-pub type SealedboxResult<T> = Result<T, ()>;
+#[derive(Debug, Clone)]
+pub struct Sealedbox {
+    public_key: PublicKey,
+    secret_key: Option<SecretKey>,
+}
 
-pub trait DecryptSealedbox: Sized {
-    fn decrypt_sealedbox_native(self, public_key: &PublicKey, secret_key: &SecretKey) -> SealedboxResult<Self>;
-    
-    #[inline]
-    fn decrypt_sealedbox(self, public_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self> {
-        self.decrypt_sealedbox_native(public_key, secret_key).map_err(|_| CodecError::InvalidData)
+impl Sealedbox {
+    pub fn new(public_key: PublicKey) -> Self {
+        Self { public_key, secret_key: None }
     }
-}
 
-pub trait DecryptSealedboxBody: DecryptSealedbox + ReadHeader + WriteHeader + UnwrapType
-{
-    #[inline]
-    fn decrypt_sealedbox_with_type<V>(self, public_key: &PublicKey, secret_key: &SecretKey, mimetype: V) -> CodecResult<Self>
-        where HeaderValue: PartialEq<V>
-    {
-        if self.is_header("Content-Type", mimetype) {
-            self.decrypt_sealedbox(public_key, secret_key)
-        } else {
-            Err(CodecError::InvalidType)
-        }
+    pub fn with_secret_key(self, secret_key: SecretKey) -> Self {
+        Self { secret_key: Some(secret_key), ..self }
     }
+}
 
-    #[inline]
-    fn decrypt_sealedbox_auto_type(self, public_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self> {
-        if let Some(mimetype) = self.unwrap_type("sealedbox") {
-            self.decrypt_sealedbox(public_key, secret_key)
-                .map(move |mut new_self| {
-                    new_self.set_header("Content-Type", mimetype);
-                    new_self
-                })
-        } else {
-            Err(CodecError::InvalidType)
-        }
+impl BodyCodec for Sealedbox {
+    fn subtype(&self) -> &str {
+        "sealedbox"
     }
 }
 
-impl DecryptSealedbox for Bytes
-{
-    fn decrypt_sealedbox_native(self, public_key: &PublicKey, secret_key: &SecretKey) -> SealedboxResult<Bytes> {
-        Ok(sealedbox::open(&self, public_key, secret_key)?.into())
+impl BodyDecoder<Bytes> for Sealedbox {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        let secret_key = self.secret_key.as_ref()
+            .ok_or_else(|| CodecError::misconfigured(self.subtype())
+                        .with_source("Secret key required to open"))?;
+        sealedbox::open(&data, &self.public_key, secret_key)
+            .map(Bytes::from)
+            .map_err(|_| CodecError::invalid_data(self.subtype())
+                     .with_source("Unable to open sealed box"))
     }
 }
 
-impl DecryptSealedbox for Request<Bytes>
-{
-    fn decrypt_sealedbox_native(self, public_key: &PublicKey, secret_key: &SecretKey) -> SealedboxResult<Self> {
-        let (parts, body) = self.into_parts();
-        let body = body.decrypt_sealedbox_native(public_key, secret_key)?;
-        Ok(Request::from_parts(parts, body))
+impl BodyEncoder<Bytes> for Sealedbox {
+    fn encode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        Ok(sealedbox::seal(&data, &self.public_key).into())
     }
 }
 
-impl DecryptSealedboxBody for Request<Bytes> {}
+pub trait DecryptSealedbox: Sized {
+    fn decrypt_sealedbox(self, public_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self>;
+}
 
-impl DecryptSealedbox for Response<Bytes>
+impl<B> DecryptSealedbox for B
+    where B: DecodeData<B, Data = Bytes>
 {
-    fn decrypt_sealedbox_native(self, public_key: &PublicKey, secret_key: &SecretKey) -> SealedboxResult<Self> {
-        let (parts, body) = self.into_parts();
-        let body = body.decrypt_sealedbox_native(public_key, secret_key)?;
-        Ok(Response::from_parts(parts, body))
+    #[inline]
+    fn decrypt_sealedbox(self, public_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self> {
+        self.decode_data(&Sealedbox::new(*public_key).with_secret_key(secret_key.clone()))
     }
 }
 
-impl DecryptSealedboxBody for Response<Bytes> {}
+pub trait DecryptSealedboxBody: DecryptSealedbox + WriteHeader
+{
+    fn decrypt_sealedbox_with_type<V>(self, public_key: &PublicKey, secret_key: &SecretKey, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern;
 
-pub trait EncryptSealedbox: Sized {
-    fn encrypt_sealedbox_native(self, public_key: &PublicKey) -> SealedboxResult<Self>;
-    
-    #[inline]
-    fn encrypt_sealedbox(self, public_key: &PublicKey) -> CodecResult<Self> {
-        self.encrypt_sealedbox_native(public_key).map_err(|_| CodecError::InvalidData)
-    }
+    fn decrypt_sealedbox_auto_type(self, public_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self>;
 }
 
-pub trait EncryptSealedboxBody: EncryptSealedbox + ReadHeader + WriteHeader + WrapType
+impl<B> DecryptSealedboxBody for B
+    where B: DecodeBody<B, Data = Bytes> + WriteHeader
 {
     #[inline]
-    fn encrypt_sealedbox_with_type(self, public_key: &PublicKey, mimetype: &'static str) -> CodecResult<Self>
+    fn decrypt_sealedbox_with_type<V>(self, public_key: &PublicKey, secret_key: &SecretKey, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern
     {
-        self.encrypt_sealedbox(public_key)
-            .map(|mut new_self| {
-                new_self.set_header("Content-Type", HeaderValue::from_static(mimetype));
-                new_self
-            })
+        self.decode_body_with_type(&Sealedbox::new(*public_key).with_secret_key(secret_key.clone()), mimetype)
     }
 
     #[inline]
-    fn encrypt_sealedbox_auto_type(self, public_key: &PublicKey) -> CodecResult<Self> {
-        if let Some(mimetype) = self.wrap_type("sealedbox") {
-            self.encrypt_sealedbox(public_key)
-                .map(move |mut new_self| {
-                    new_self.set_header("Content-Type", mimetype);
-                    new_self
-                })
-        } else {
-            Err(CodecError::InvalidType)
-        }
+    fn decrypt_sealedbox_auto_type(self, public_key: &PublicKey, secret_key: &SecretKey) -> CodecResult<Self> {
+        self.decode_body_auto_type(&Sealedbox::new(*public_key).with_secret_key(secret_key.clone()))
     }
 }
 
-impl EncryptSealedbox for Bytes
-{
-    fn encrypt_sealedbox_native(self, public_key: &PublicKey) -> SealedboxResult<Self> {
-        Ok(sealedbox::seal(&self, public_key).into())
-    }
+pub trait EncryptSealedbox: Sized {
+    fn encrypt_sealedbox(self, public_key: &PublicKey) -> CodecResult<Self>;
 }
 
-impl EncryptSealedbox for Request<Bytes>
+impl<B> EncryptSealedbox for B
+    where B: EncodeData<B, Data = Bytes>
 {
-    fn encrypt_sealedbox_native(self, public_key: &PublicKey) -> SealedboxResult<Self> {
-        let (parts, body) = self.into_parts();
-        let body = body.encrypt_sealedbox_native(public_key)?;
-        Ok(Request::from_parts(parts, body))
+    #[inline]
+    fn encrypt_sealedbox(self, public_key: &PublicKey) -> CodecResult<Self> {
+        self.encode_data(&Sealedbox::new(*public_key))
     }
 }
 
-impl EncryptSealedboxBody for Request<Bytes> {}
+pub trait EncryptSealedboxBody: EncryptSealedbox + WriteHeader
+{
+    fn encrypt_sealedbox_with_type(self, public_key: &PublicKey, mimetype: &'static str) -> CodecResult<Self>;
+
+    fn encrypt_sealedbox_auto_type(self, public_key: &PublicKey) -> CodecResult<Self>;
+}
 
-impl EncryptSealedbox for Response<Bytes>
+impl<B> EncryptSealedboxBody for B
+    where B: EncodeBody<B, Data = Bytes> + WriteHeader
 {
-    fn encrypt_sealedbox_native(self, public_key: &PublicKey) -> SealedboxResult<Self> {
-        let (parts, body) = self.into_parts();
-        let body = body.encrypt_sealedbox_native(public_key)?;
-        Ok(Response::from_parts(parts, body))
+    #[inline]
+    fn encrypt_sealedbox_with_type(self, public_key: &PublicKey, mimetype: &'static str) -> CodecResult<Self> {
+        self.encode_body_with_type(&Sealedbox::new(*public_key), mimetype)
     }
-}
 
-impl EncryptSealedboxBody for Response<Bytes> {}
+    #[inline]
+    fn encrypt_sealedbox_auto_type(self, public_key: &PublicKey) -> CodecResult<Self> {
+        self.encode_body_auto_type(&Sealedbox::new(*public_key))
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use http::{Request};
+    use http::{Request, StatusCode};
     use super::super::gen_keypair;
+    use super::super::{ReadHeader, CodecErrorKind};
     use super::*;
 
     #[test]
@@ -160,4 +139,14 @@ mod tests {
         
         assert_eq!(d.into_body(), "hello world");
     }
+
+    #[test]
+    fn test_sealedbox_err_key() {
+        let (pk, _) = gen_keypair();
+        let e = Bytes::from("hello").encrypt_sealedbox(&pk).unwrap();
+        let e = e.decode_data(&Sealedbox::new(pk)).map(|_: Bytes| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::Misconfigured);
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 }
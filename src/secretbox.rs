@@ -0,0 +1,165 @@
+use bytes::{Bytes};
+use sodiumoxide::crypto::secretbox::{self, Nonce, NONCEBYTES, MACBYTES};
+use super::{WriteHeader, AsTypePattern, CodecError, CodecResult, BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody, Key};
+
+#[derive(Debug, Clone)]
+pub struct Secretbox {
+    key: Key,
+}
+
+impl Secretbox {
+    pub fn new(key: Key) -> Self {
+        Self { key }
+    }
+}
+
+impl BodyCodec for Secretbox {
+    fn subtype(&self) -> &str {
+        "secretbox"
+    }
+}
+
+impl BodyDecoder<Bytes> for Secretbox {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        let nonce = data.get(..NONCEBYTES).and_then(Nonce::from_slice)
+            .ok_or_else(|| CodecError::invalid_data(self.subtype())
+                        .with_source("Missing nonce"))?;
+        secretbox::open(&data[NONCEBYTES..], &nonce, &self.key)
+            .map(Bytes::from)
+            .map_err(|_| CodecError::invalid_data(self.subtype())
+                     .with_source("Unable to open secret box"))
+    }
+}
+
+impl BodyEncoder<Bytes> for Secretbox {
+    fn encode_bytes(&self, data: Bytes) -> CodecResult<Bytes> {
+        let nonce = secretbox::gen_nonce();
+        let mut body = Vec::with_capacity(NONCEBYTES + data.len() + MACBYTES);
+        body.extend_from_slice(&nonce.0);
+        body.extend_from_slice(&secretbox::seal(&data, &nonce, &self.key));
+        Ok(body.into())
+    }
+}
+
+pub trait DecryptSecretbox: Sized {
+    fn decrypt_secretbox(self, key: &Key) -> CodecResult<Self>;
+}
+
+impl<B> DecryptSecretbox for B
+    where B: DecodeData<B, Data = Bytes>
+{
+    #[inline]
+    fn decrypt_secretbox(self, key: &Key) -> CodecResult<Self> {
+        self.decode_data(&Secretbox::new(key.clone()))
+    }
+}
+
+pub trait DecryptSecretboxBody: DecryptSecretbox + WriteHeader
+{
+    fn decrypt_secretbox_with_type<V>(self, key: &Key, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern;
+
+    fn decrypt_secretbox_auto_type(self, key: &Key) -> CodecResult<Self>;
+}
+
+impl<B> DecryptSecretboxBody for B
+    where B: DecodeBody<B, Data = Bytes> + WriteHeader
+{
+    #[inline]
+    fn decrypt_secretbox_with_type<V>(self, key: &Key, mimetype: V) -> CodecResult<Self>
+        where V: AsTypePattern
+    {
+        self.decode_body_with_type(&Secretbox::new(key.clone()), mimetype)
+    }
+
+    #[inline]
+    fn decrypt_secretbox_auto_type(self, key: &Key) -> CodecResult<Self> {
+        self.decode_body_auto_type(&Secretbox::new(key.clone()))
+    }
+}
+
+pub trait EncryptSecretbox: Sized {
+    fn encrypt_secretbox(self, key: &Key) -> CodecResult<Self>;
+}
+
+impl<B> EncryptSecretbox for B
+    where B: EncodeData<B, Data = Bytes>
+{
+    #[inline]
+    fn encrypt_secretbox(self, key: &Key) -> CodecResult<Self> {
+        self.encode_data(&Secretbox::new(key.clone()))
+    }
+}
+
+pub trait EncryptSecretboxBody: EncryptSecretbox + WriteHeader
+{
+    fn encrypt_secretbox_with_type(self, key: &Key, mimetype: &'static str) -> CodecResult<Self>;
+
+    fn encrypt_secretbox_auto_type(self, key: &Key) -> CodecResult<Self>;
+}
+
+impl<B> EncryptSecretboxBody for B
+    where B: EncodeBody<B, Data = Bytes> + WriteHeader
+{
+    #[inline]
+    fn encrypt_secretbox_with_type(self, key: &Key, mimetype: &'static str) -> CodecResult<Self> {
+        self.encode_body_with_type(&Secretbox::new(key.clone()), mimetype)
+    }
+
+    #[inline]
+    fn encrypt_secretbox_auto_type(self, key: &Key) -> CodecResult<Self> {
+        self.encode_body_auto_type(&Secretbox::new(key.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request};
+    use super::super::gen_key;
+    use super::super::{ReadHeader, CodecErrorKind};
+    use super::*;
+
+    #[test]
+    fn test_secretbox() {
+        let key = gen_key();
+        
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/vnd.literium.v1+plain")
+            .body("hello world".into())
+            .unwrap();
+        
+        let e = a.encrypt_secretbox_auto_type(&key).unwrap();
+
+        assert!(e.is_header("Content-Type", "application/vnd.literium.v1+plain+secretbox"));
+        assert_eq!(e.body().len(), NONCEBYTES + MACBYTES + 11);
+        
+        let d = e.decrypt_secretbox_auto_type(&key).unwrap();
+
+        assert!(d.is_header("Content-Type", "application/vnd.literium.v1+plain"));
+        
+        assert_eq!(d.into_body(), "hello world");
+    }
+
+    #[test]
+    fn test_secretbox_err_key() {
+        let a: Request<Bytes> = Request::builder()
+            .body("hello world".into())
+            .unwrap();
+        
+        let e = a.encrypt_secretbox(&gen_key()).unwrap();
+        let d = e.decrypt_secretbox(&gen_key());
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_secretbox_err_short() {
+        let a: Request<Bytes> = Request::builder()
+            .body("hello".into())
+            .unwrap();
+        
+        let d = a.decrypt_secretbox(&gen_key());
+
+        assert_eq!(d.unwrap_err().kind(), CodecErrorKind::InvalidData);
+    }
+}
@@ -17,3 +17,39 @@ where D: Deserializer<'de>, T: FromBinary + Sized
         .and_then(|b| T::from_binary(b.as_ref())
                   .ok_or_else(|| de::Error::custom("Invalid value size")))
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use super::super::{PublicKey};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Data {
+        #[serde(with = "super")]
+        data: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Peer {
+        #[serde(with = "super")]
+        key: PublicKey,
+    }
+
+    #[test]
+    fn test_base64_roundtrip() {
+        let a = Data { data: vec![0xfb, 0xff, 0xbf, 0x01] };
+        let e = serde_json::to_string(&a).unwrap();
+
+        assert_eq!(e, r#"{"data":"+/+/AQ=="}"#);
+
+        let d: Data = serde_json::from_str(&e).unwrap();
+
+        assert_eq!(d, a);
+    }
+
+    #[test]
+    fn test_base64_err() {
+        assert!(serde_json::from_str::<Data>(r#"{"data":"-_-_AQ"}"#).is_err());
+        assert!(serde_json::from_str::<Peer>(r#"{"key":"+/+/AQ=="}"#).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Serializer, de, Deserialize, Deserializer};
+use base64lib;
+use super::{IntoBinary, FromBinary};
+
+pub fn serialize<S, T>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where S: Serializer, T: IntoBinary
+{
+    let b = base64lib::encode_config(bytes.into_binary(), base64lib::URL_SAFE_NO_PAD);
+    serializer.serialize_str(&b)
+}
+
+pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where D: Deserializer<'de>, T: FromBinary + Sized
+{
+    let s = <&str>::deserialize(deserializer)?;
+    base64lib::decode_config(s, base64lib::URL_SAFE_NO_PAD).map_err(de::Error::custom)
+        .and_then(|b| T::from_binary(b.as_ref())
+                  .ok_or_else(|| de::Error::custom("Invalid value size")))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use super::super::{PublicKey};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Data {
+        #[serde(with = "super")]
+        data: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Peer {
+        #[serde(with = "super")]
+        key: PublicKey,
+    }
+
+    #[test]
+    fn test_base64url_roundtrip() {
+        let a = Data { data: vec![0xfb, 0xff, 0xbf, 0x01] };
+        let e = serde_json::to_string(&a).unwrap();
+
+        assert_eq!(e, r#"{"data":"-_-_AQ"}"#);
+
+        let d: Data = serde_json::from_str(&e).unwrap();
+
+        assert_eq!(d, a);
+    }
+
+    #[test]
+    fn test_base64url_err() {
+        assert!(serde_json::from_str::<Data>(r#"{"data":"+/+/AQ=="}"#).is_err());
+        assert!(serde_json::from_str::<Peer>(r#"{"key":"-_-_AQ"}"#).is_err());
+    }
+}
@@ -0,0 +1,408 @@
+use std::char;
+use http::header::{HeaderValue};
+use bytes::{Bytes};
+use super::{WriteHeader, AsTypePattern, ContentType, CodecError, CodecPosition, CodecResult,
+            BodyCodec, BodyDecoder, BodyEncoder, DecodeData, DecodeBody, EncodeData, EncodeBody};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    // big endian unless byte order mark says otherwise
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Ascii,
+}
+
+impl Charset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Charset::Utf8),
+            "utf-16" | "utf16" => Some(Charset::Utf16),
+            "utf-16le" => Some(Charset::Utf16Le),
+            "utf-16be" => Some(Charset::Utf16Be),
+            "iso-8859-1" | "iso_8859-1" | "latin1" | "l1" => Some(Charset::Latin1),
+            "us-ascii" | "ascii" => Some(Charset::Ascii),
+            _ => None,
+        }
+    }
+
+    // the missing charset means utf-8
+    pub fn from_content_type(mimetype: &str) -> CodecResult<Self> {
+        let ct = ContentType::parse(mimetype)
+            .map_err(|error| CodecError::invalid_type("plain").with_source(error))?;
+        match ct.get_param("charset") {
+            Some(name) => Charset::from_name(name)
+                .ok_or_else(|| CodecError::invalid_type("plain")
+                            .with_source(format!("Unsupported charset {}", name))),
+            None => Ok(Charset::Utf8),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "utf-8",
+            Charset::Utf16 => "utf-16",
+            Charset::Utf16Le => "utf-16le",
+            Charset::Utf16Be => "utf-16be",
+            Charset::Latin1 => "iso-8859-1",
+            Charset::Ascii => "us-ascii",
+        }
+    }
+}
+
+fn decode_utf16(data: &[u8], big_endian: bool, offset: usize) -> CodecResult<String> {
+    let units = data.chunks_exact(2);
+    if !units.remainder().is_empty() {
+        return Err(CodecError::invalid_data("plain")
+                   .with_position(CodecPosition::Offset(offset + data.len() - 1))
+                   .with_source("Odd number of bytes"));
+    }
+    let units = units.map(|unit| if big_endian {
+        u16::from(unit[0]) << 8 | u16::from(unit[1])
+    } else {
+        u16::from(unit[1]) << 8 | u16::from(unit[0])
+    });
+    let mut text = String::with_capacity(data.len() / 2);
+    let mut position = offset;
+    for chr in char::decode_utf16(units) {
+        match chr {
+            Ok(chr) => {
+                position += chr.len_utf16() * 2;
+                text.push(chr);
+            },
+            Err(error) => return Err(CodecError::invalid_data("plain")
+                                     .with_position(CodecPosition::Offset(position))
+                                     .with_source(error)),
+        }
+    }
+    Ok(text)
+}
+
+fn encode_utf16(data: &str, big_endian: bool) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len() * 2);
+    for unit in data.encode_utf16() {
+        let [high, low] = [(unit >> 8) as u8, unit as u8];
+        if big_endian {
+            bytes.extend_from_slice(&[high, low]);
+        } else {
+            bytes.extend_from_slice(&[low, high]);
+        }
+    }
+    bytes
+}
+
+// the position is a byte offset in the source string
+fn encode_8bit(data: &str, max: char) -> CodecResult<Vec<u8>> {
+    data.char_indices().map(|(offset, chr)| if chr <= max {
+        Ok(chr as u8)
+    } else {
+        Err(CodecError::encode_failed("plain")
+            .with_position(CodecPosition::Offset(offset))
+            .with_source(format!("Character {:?} cannot be encoded", chr)))
+    }).collect()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Text {
+    charset: Charset,
+}
+
+impl Text {
+    pub fn new(charset: Charset) -> Self {
+        Text { charset }
+    }
+
+    pub fn charset(&self) -> Charset {
+        self.charset
+    }
+}
+
+impl BodyCodec for Text {
+    fn subtype(&self) -> &str {
+        "plain"
+    }
+}
+
+impl BodyDecoder<String> for Text {
+    fn decode_bytes(&self, data: Bytes) -> CodecResult<String> {
+        match self.charset {
+            // the leading byte order mark is not a part of text
+            Charset::Utf8 => {
+                let skip = if data.starts_with(b"\xef\xbb\xbf") { 3 } else { 0 };
+                String::from_utf8(data[skip..].to_vec()).map_err(|error| {
+                    let position = skip + error.utf8_error().valid_up_to();
+                    CodecError::invalid_data(self.subtype())
+                        .with_position(CodecPosition::Offset(position))
+                        .with_source(error)
+                })
+            },
+            Charset::Utf16 => match data.get(..2) {
+                Some(b"\xfe\xff") => decode_utf16(&data[2..], true, 2),
+                Some(b"\xff\xfe") => decode_utf16(&data[2..], false, 2),
+                _ => decode_utf16(&data, true, 0),
+            },
+            Charset::Utf16Le => decode_utf16(&data, false, 0),
+            Charset::Utf16Be => decode_utf16(&data, true, 0),
+            Charset::Latin1 => Ok(data.iter().map(|byte| char::from(*byte)).collect()),
+            Charset::Ascii => match data.iter().position(|byte| !byte.is_ascii()) {
+                Some(position) => Err(CodecError::invalid_data(self.subtype())
+                                      .with_position(CodecPosition::Offset(position))),
+                None => Ok(data.iter().map(|byte| char::from(*byte)).collect()),
+            },
+        }
+    }
+}
+
+impl BodyEncoder<String> for Text {
+    fn encode_bytes(&self, data: String) -> CodecResult<Bytes> {
+        Ok(match self.charset {
+            Charset::Utf8 => data.into(),
+            Charset::Utf16 | Charset::Utf16Be => encode_utf16(&data, true).into(),
+            Charset::Utf16Le => encode_utf16(&data, false).into(),
+            Charset::Latin1 => encode_8bit(&data, '\u{ff}')?.into(),
+            Charset::Ascii => encode_8bit(&data, '\u{7f}')?.into(),
+        })
+    }
+}
+
+pub trait DecodeText<T>: Sized {
+    fn decode_text(self) -> CodecResult<T>;
+
+    fn decode_text_charset(self, charset: Charset) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeText<T> for B
+    where B: DecodeData<T, Data = String>
+{
+    #[inline]
+    fn decode_text(self) -> CodecResult<T> {
+        self.decode_data(&Text::default())
+    }
+
+    #[inline]
+    fn decode_text_charset(self, charset: Charset) -> CodecResult<T> {
+        self.decode_data(&Text::new(charset))
+    }
+}
+
+// the charset is taken from content type,
+// the text is terminal so the type is matched but not unwrapped
+pub trait DecodeTextBody<T>: DecodeText<T>
+    where T: WriteHeader
+{
+    fn decode_text_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern;
+
+    fn decode_text_any_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> DecodeTextBody<T> for B
+    where B: DecodeBody<T, Data = String>,
+          T: WriteHeader
+{
+    #[inline]
+    fn decode_text_with_type<V>(self, mimetype: V) -> CodecResult<T>
+        where V: AsTypePattern
+    {
+        let charset = Charset::from_content_type(self.get_header_str("Content-Type").unwrap_or(""))?;
+        self.decode_body_with_type(&Text::new(charset), mimetype)
+    }
+
+    #[inline]
+    fn decode_text_any_type(self) -> CodecResult<T> {
+        self.decode_text_with_type("text/*")
+    }
+}
+
+pub trait EncodeText<T>: Sized {
+    fn encode_text(self) -> CodecResult<T>;
+
+    fn encode_text_charset(self, charset: Charset) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeText<T> for B
+    where B: EncodeData<T, Data = String>
+{
+    #[inline]
+    fn encode_text(self) -> CodecResult<T> {
+        self.encode_data(&Text::default())
+    }
+
+    #[inline]
+    fn encode_text_charset(self, charset: Charset) -> CodecResult<T> {
+        self.encode_data(&Text::new(charset))
+    }
+}
+
+// the charset is taken from the given content type,
+// the auto type is text/plain in utf-8
+pub trait EncodeTextBody<T>: EncodeText<T>
+    where T: WriteHeader
+{
+    fn encode_text_with_type(self, mimetype: &'static str) -> CodecResult<T>;
+
+    fn encode_text_auto_type(self) -> CodecResult<T>;
+}
+
+impl<T, B> EncodeTextBody<T> for B
+    where B: EncodeBody<T, Data = String>,
+          T: WriteHeader
+{
+    #[inline]
+    fn encode_text_with_type(self, mimetype: &'static str) -> CodecResult<T> {
+        // the unsupported charset is chosen by server
+        let charset = Charset::from_content_type(mimetype)
+            .map_err(|error| CodecError::encode_failed("plain").with_source(error))?;
+        self.encode_body_with_type(&Text::new(charset), mimetype)
+    }
+
+    #[inline]
+    fn encode_text_auto_type(self) -> CodecResult<T> {
+        self.encode_data(&Text::default()).map(|mut new_self| {
+            new_self.set_header("Content-Type", HeaderValue::from_static("text/plain; charset=utf-8"));
+            new_self
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Request, Response};
+    use super::super::{CodecErrorKind, ReadHeader};
+    use super::*;
+
+    #[test]
+    fn test_decode_text_ok() {
+        let a: Request<Bytes> = Request::builder()
+            .body("привет".into())
+            .unwrap();
+        let d: Request<String> = a.decode_text().unwrap();
+
+        assert_eq!(d.into_body(), "привет");
+    }
+
+    #[test]
+    fn test_decode_text_err_data() {
+        let a: Request<Bytes> = Request::builder()
+            .body(Bytes::from(&b"abc\xff"[..]))
+            .unwrap();
+        let e = a.decode_text().map(|_: Request<String>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidData);
+        assert_eq!(e.position(), Some(CodecPosition::Offset(3)));
+    }
+
+    #[test]
+    fn test_decode_text_bom() {
+        let a: Request<Bytes> = Request::builder()
+            .body(Bytes::from(&b"\xef\xbb\xbfhi"[..]))
+            .unwrap();
+        let d: Request<String> = a.decode_text().unwrap();
+
+        assert_eq!(d.into_body(), "hi");
+
+        let a: Request<Bytes> = Request::builder()
+            .body(Bytes::from(&b"\xef\xbb\xbfhi\xff"[..]))
+            .unwrap();
+        let e = a.decode_text().map(|_: Request<String>| ()).unwrap_err();
+
+        assert_eq!(e.position(), Some(CodecPosition::Offset(5)));
+    }
+
+    #[test]
+    fn test_decode_text_with_type_latin1() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "text/plain; charset=ISO-8859-1")
+            .body(Bytes::from(&b"caf\xe9"[..]))
+            .unwrap();
+        let d: Request<String> = a.decode_text_any_type().unwrap();
+
+        assert_eq!(d.into_body(), "café");
+    }
+
+    #[test]
+    fn test_decode_text_with_type_utf16() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "text/csv; charset=utf-16")
+            .body(Bytes::from(&b"\xff\xfeh\0i\0"[..]))
+            .unwrap();
+        let d: Request<String> = a.decode_text_with_type("text/csv").unwrap();
+
+        assert_eq!(d.into_body(), "hi");
+
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "text/plain; charset=utf-16be")
+            .body(Bytes::from(&b"\xd8\x00\0h"[..]))
+            .unwrap();
+        let e = a.decode_text_any_type().map(|_: Request<String>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidData);
+        assert_eq!(e.position(), Some(CodecPosition::Offset(0)));
+    }
+
+    #[test]
+    fn test_decode_text_err_type() {
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "text/plain; charset=koi8-r")
+            .body("hi".into())
+            .unwrap();
+        let e = a.decode_text_any_type().map(|_: Request<String>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidType);
+
+        let a: Request<Bytes> = Request::builder()
+            .header("Content-Type", "application/json")
+            .body("hi".into())
+            .unwrap();
+        let e = a.decode_text_any_type().map(|_: Request<String>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::InvalidType);
+    }
+
+    #[test]
+    fn test_encode_text_auto_type() {
+        let a = Response::builder()
+            .body(String::from("привет"))
+            .unwrap();
+        let d: Response<Bytes> = a.encode_text_auto_type().unwrap();
+
+        assert!(d.is_header("Content-Type", "text/plain; charset=utf-8"));
+        assert_eq!(d.into_body(), "привет");
+    }
+
+    #[test]
+    fn test_encode_text_with_type() {
+        let a = Response::builder()
+            .body(String::from("hé"))
+            .unwrap();
+        let d: Response<Bytes> = a.encode_text_with_type("text/html; charset=utf-16le").unwrap();
+
+        assert!(d.is_header("Content-Type", "text/html; charset=utf-16le"));
+        assert_eq!(d.into_body(), &b"h\0\xe9\0"[..]);
+
+        let a = Response::builder()
+            .body(String::from("привет"))
+            .unwrap();
+        let e = a.encode_text_with_type("text/plain; charset=latin1").map(|_: Response<Bytes>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::EncodeFailed);
+        assert_eq!(e.position(), Some(CodecPosition::Offset(0)));
+
+        let a = Response::builder()
+            .body(String::from("hé€"))
+            .unwrap();
+        let e = a.encode_text_with_type("text/plain; charset=latin1").map(|_: Response<Bytes>| ()).unwrap_err();
+
+        assert_eq!(e.position(), Some(CodecPosition::Offset(3)));
+
+        let a = Response::builder()
+            .body(String::from("hi"))
+            .unwrap();
+        let e = a.encode_text_with_type("text/plain; charset=koi8-r").map(|_: Response<Bytes>| ()).unwrap_err();
+
+        assert_eq!(e.kind(), CodecErrorKind::EncodeFailed);
+    }
+}
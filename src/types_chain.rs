@@ -1,35 +1,86 @@
+use std::str;
 use http::{Request, Response, HttpTryFrom};
 use http::header::{HeaderValue};
-use super::{ReadHeader, ContentType};
+use super::{ReadHeader, ContentType, ContentTypeError};
+
+// The pattern of content type given as string or header value.
+pub trait AsTypePattern {
+    fn as_type_pattern(&self) -> Option<&str>;
+}
+
+impl AsTypePattern for str {
+    fn as_type_pattern(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl AsTypePattern for String {
+    fn as_type_pattern(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl AsTypePattern for [u8] {
+    fn as_type_pattern(&self) -> Option<&str> {
+        str::from_utf8(self).ok()
+    }
+}
+
+impl AsTypePattern for HeaderValue {
+    fn as_type_pattern(&self) -> Option<&str> {
+        self.to_str().ok()
+    }
+}
+
+impl<'ct> AsTypePattern for ContentType<'ct> {
+    fn as_type_pattern(&self) -> Option<&str> {
+        Some(self.as_ref())
+    }
+}
+
+impl<T> AsTypePattern for &T
+    where T: AsTypePattern + ?Sized
+{
+    fn as_type_pattern(&self) -> Option<&str> {
+        (**self).as_type_pattern()
+    }
+}
+
+pub trait MatchType: ReadHeader {
+    fn match_type(&self, pattern: &str) -> bool {
+        match (self.get_header_str("Content-Type").map(ContentType::parse), ContentType::parse(pattern)) {
+            (Some(Ok(ct)), Ok(pattern)) => ct.matches(&pattern),
+            _ => false,
+        }
+    }
+}
 
 pub trait UnwrapType: ReadHeader {
-    fn unwrap_type(&self, subtype: &str) -> Option<HeaderValue> {
+    fn unwrap_type(&self, subtype: &str) -> Result<Option<HeaderValue>, ContentTypeError> {
         if let Some(mimetype) = self.get_header_str("Content-Type") {
-            let mut ct = ContentType::new(mimetype);
+            let mut ct = ContentType::parse(mimetype)?;
             if ct.last_subtype() == Some(subtype) {
                 ct.pop_subtype();
-                if let Ok(mimetype) = HeaderValue::try_from(ct.as_ref()) {
-                    return Some(mimetype);
-                }
+                return Ok(HeaderValue::try_from(ct.to_string().as_str()).ok());
             }
         }
-        None
+        Ok(None)
     }
 }
 
 pub trait WrapType: ReadHeader {
-    fn wrap_type(&self, subtype: &str) -> Option<HeaderValue> {
+    fn wrap_type(&self, subtype: &str) -> Result<Option<HeaderValue>, ContentTypeError> {
         if let Some(mimetype) = self.get_header_str("Content-Type") {
-            let mut ct = ContentType::new(mimetype);
+            let mut ct = ContentType::parse(mimetype)?;
             ct.push_subtype(subtype);
-            if let Ok(mimetype) = HeaderValue::try_from(ct.as_ref()) {
-                return Some(mimetype);
-            }
+            return Ok(HeaderValue::try_from(ct.to_string().as_str()).ok());
         }
-        None
+        Ok(None)
     }
 }
 
+impl<T> MatchType for Request<T> {}
+impl<T> MatchType for Response<T> {}
 impl<T> UnwrapType for Request<T> {}
 impl<T> UnwrapType for Response<T> {}
 impl<T> WrapType for Request<T> {}